@@ -13,6 +13,8 @@ function ri_setup {
   export ri_target_triple="x86_64-unknown-rite-gnu"
   # Rust libcore location
   export ri_libcore="libcore"
+  # Cargo features, like "slab_poison"
+  export ri_features="${RITE_FEATURES:-}"
 }
 
 function ri_assemble {
@@ -66,10 +68,10 @@ function ri_verify-multiboot2 {
 function ri_build-kernel {
   printf "Compiling kernel... "
   export RUSTFLAGS="-L $ri_libcore/target/$ri_target_triple/release"
-  if ! cargo build --release \
+  if ! cargo build --release --features "$ri_features" \
     --target $ri_target_triple.json &>/dev/null; then
     printf "FAIL\n"
-    cargo build --release --target $ri_target_triple.json
+    cargo build --release --features "$ri_features" --target $ri_target_triple.json
     exit 5
   fi
   printf "OK\n"
@@ -101,11 +103,13 @@ function compile {
   ri_setup
   ri_assemble \
     "multiboot.asm" \
-    "boot.asm"
+    "boot.asm" \
+    "interrupts.asm"
   ri_build-kernel
   ri_link \
     "multiboot.o" \
     "boot.o" \
+    "interrupts.o" \
     "lib$ri_kernel.a"
   ri_verify-multiboot2
   ri_build-iso
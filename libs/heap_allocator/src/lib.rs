@@ -0,0 +1,224 @@
+#![feature(allocator)]
+#![feature(const_fn)]
+#![allocator]
+#![no_std]
+
+extern crate spin;
+
+use core::mem;
+use core::ptr;
+use spin::Mutex;
+
+/// The kernel heap.
+///
+/// Unusable until `init` was called.
+static HEAP: Mutex<Option<Heap>> = Mutex::new(None);
+
+/// Initializes the kernel heap.
+///
+/// The memory from `start` to `start + size` must be
+/// mapped and must not be used for anything else.
+pub unsafe fn init(start: usize, size: usize) {
+    *HEAP.lock() = Some(Heap::new(start, size));
+}
+
+/// The `Hole` type.
+///
+/// Represents a free region of the heap.
+/// Holes are stored inside the free memory they describe.
+struct Hole {
+    /// The size of the hole, including this header.
+    size: usize,
+
+    /// The next hole, in ascending address order.
+    next: *mut Hole,
+}
+
+/// The `Heap` type.
+///
+/// A first-fit allocator that keeps the free memory
+/// in a linked list of holes, sorted by address.
+struct Heap {
+    /// The dummy head of the hole list.
+    head: Hole,
+}
+
+/// The `Send` implementation for `Heap`.
+///
+/// The holes are only ever accessed through the heap lock.
+unsafe impl Send for Heap {}
+
+/// The `Heap` implementation.
+impl Heap {
+    /// The smallest block the heap hands out.
+    ///
+    /// Every freed block must be able to hold a `Hole`.
+    fn min_size() -> usize {
+        mem::size_of::<Hole>()
+    }
+
+    /// Constructs a new `Heap` covering the specified memory.
+    unsafe fn new(start: usize, size: usize) -> Heap {
+        let aligned_start = align_up(start, mem::align_of::<Hole>());
+        let size = size - (aligned_start - start);
+        let start = aligned_start;
+        let hole = start as *mut Hole;
+        ptr::write(hole,
+                   Hole {
+                       size: size,
+                       next: ptr::null_mut(),
+                   });
+        Heap {
+            head: Hole {
+                size: 0,
+                next: hole,
+            },
+        }
+    }
+
+    /// Rounds a requested size up to something the heap can free again.
+    fn block_size(size: usize) -> usize {
+        let size = if size < Heap::min_size() {
+            Heap::min_size()
+        } else {
+            size
+        };
+        align_up(size, mem::align_of::<Hole>())
+    }
+
+    /// Allocates a block using the first hole that fits.
+    unsafe fn allocate(&mut self, size: usize, align: usize) -> *mut u8 {
+        let size = Heap::block_size(size);
+        let mut previous: *mut Hole = &mut self.head;
+        while !(*previous).next.is_null() {
+            let hole = (*previous).next;
+            let hole_start = hole as usize;
+            let hole_end = hole_start + (*hole).size;
+
+            // Leave either no front padding or enough to form a hole
+            let mut start = align_up(hole_start, align);
+            if start != hole_start && start - hole_start < Heap::min_size() {
+                start = align_up(hole_start + Heap::min_size(), align);
+            }
+            let end = start + size;
+
+            // Test if the block fits and leaves a usable back padding
+            if end > hole_end || (end != hole_end && hole_end - end < Heap::min_size()) {
+                previous = hole;
+                continue;
+            }
+
+            // Replace the hole with its front and back padding
+            let mut next = (*hole).next;
+            if end != hole_end {
+                let back = end as *mut Hole;
+                ptr::write(back,
+                           Hole {
+                               size: hole_end - end,
+                               next: next,
+                           });
+                next = back;
+            }
+            if start != hole_start {
+                (*hole).size = start - hole_start;
+                (*hole).next = next;
+            } else {
+                (*previous).next = next;
+            }
+            return start as *mut u8;
+        }
+        ptr::null_mut()
+    }
+
+    /// Deallocates a block and merges it with adjacent holes.
+    unsafe fn deallocate(&mut self, ptr: *mut u8, size: usize) {
+        let size = Heap::block_size(size);
+        let start = ptr as usize;
+        let end = start + size;
+
+        // Find the last hole before the block
+        let mut previous: *mut Hole = &mut self.head;
+        while !(*previous).next.is_null() && ((*previous).next as usize) < start {
+            previous = (*previous).next;
+        }
+        let next = (*previous).next;
+
+        // Merge with the following hole
+        let mut hole = Hole {
+            size: size,
+            next: next,
+        };
+        if !next.is_null() && next as usize == end {
+            hole.size += (*next).size;
+            hole.next = (*next).next;
+        }
+
+        // Merge with the preceding hole
+        let previous_end = previous as usize + (*previous).size;
+        if previous != &mut self.head as *mut Hole && previous_end == start {
+            (*previous).size += hole.size;
+            (*previous).next = hole.next;
+        } else {
+            let block = start as *mut Hole;
+            ptr::write(block, hole);
+            (*previous).next = block;
+        }
+    }
+}
+
+/// Aligns an address upwards.
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+/// Allocates memory.
+#[no_mangle]
+pub extern "C" fn __rust_allocate(size: usize, align: usize) -> *mut u8 {
+    match *HEAP.lock() {
+        Some(ref mut heap) => unsafe { heap.allocate(size, align) },
+        None => panic!("The kernel heap is not initialized"),
+    }
+}
+
+/// Deallocates memory.
+#[no_mangle]
+pub extern "C" fn __rust_deallocate(ptr: *mut u8, size: usize, _align: usize) {
+    match *HEAP.lock() {
+        Some(ref mut heap) => unsafe { heap.deallocate(ptr, size) },
+        None => panic!("The kernel heap is not initialized"),
+    }
+}
+
+/// Gets the usable size of an allocation.
+#[no_mangle]
+pub extern "C" fn __rust_usable_size(size: usize, _align: usize) -> usize {
+    size
+}
+
+/// Reallocates memory in place.
+///
+/// Never succeeds, so callers always fall back to `__rust_reallocate`.
+#[no_mangle]
+pub extern "C" fn __rust_reallocate_inplace(_ptr: *mut u8,
+                                            size: usize,
+                                            _new_size: usize,
+                                            _align: usize)
+                                            -> usize {
+    size
+}
+
+/// Reallocates memory.
+#[no_mangle]
+pub extern "C" fn __rust_reallocate(ptr: *mut u8,
+                                    size: usize,
+                                    new_size: usize,
+                                    align: usize)
+                                    -> *mut u8 {
+    let new_ptr = __rust_allocate(new_size, align);
+    if !new_ptr.is_null() {
+        let count = if size < new_size { size } else { new_size };
+        unsafe { ptr::copy_nonoverlapping(ptr, new_ptr, count) };
+        __rust_deallocate(ptr, size, align);
+    }
+    new_ptr
+}
@@ -0,0 +1,289 @@
+use core::mem::size_of;
+use collections::Vec;
+use memory::MEMORY_CONTROLLER;
+use memory::paging::{PhysicalAddress, VirtualAddress, EntryFlags};
+
+/// The `Rsdp` type.
+///
+/// Represents the root system description pointer.
+#[repr(C, packed)]
+struct Rsdp {
+    /// The signature, `"RSD PTR "`.
+    signature: [u8; 8],
+
+    /// The checksum of the ACPI 1.0 part.
+    checksum: u8,
+
+    /// The OEM ID.
+    oem_id: [u8; 6],
+
+    /// The revision.
+    revision: u8,
+
+    /// The physical address of the RSDT.
+    rsdt_address: u32,
+
+    /// The length of the table, since ACPI 2.0.
+    length: u32,
+
+    /// The physical address of the XSDT, since ACPI 2.0.
+    xsdt_address: u64,
+
+    /// The checksum of the whole table, since ACPI 2.0.
+    extended_checksum: u8,
+
+    /// Reserved.
+    reserved: [u8; 3],
+}
+
+/// The `SdtHeader` type.
+///
+/// Represents the header every system description table starts with.
+#[repr(C, packed)]
+pub struct SdtHeader {
+    /// The signature.
+    pub signature: [u8; 4],
+
+    /// The length of the table, including the header.
+    pub length: u32,
+
+    /// The revision.
+    pub revision: u8,
+
+    /// The checksum.
+    pub checksum: u8,
+
+    /// The OEM ID.
+    pub oem_id: [u8; 6],
+
+    /// The OEM table ID.
+    pub oem_table_id: [u8; 8],
+
+    /// The OEM revision.
+    pub oem_revision: u32,
+
+    /// The creator ID.
+    pub creator_id: u32,
+
+    /// The creator revision.
+    pub creator_revision: u32,
+}
+
+/// The `MadtLocalApic` type.
+///
+/// Represents a processor and its local APIC.
+#[derive(Debug, Copy, Clone)]
+pub struct MadtLocalApic {
+    /// The ACPI processor ID.
+    pub processor_id: u8,
+
+    /// The local APIC ID.
+    pub apic_id: u8,
+
+    /// Whether the processor can be used.
+    pub enabled: bool,
+}
+
+/// The `MadtIoApic` type.
+#[derive(Debug, Copy, Clone)]
+pub struct MadtIoApic {
+    /// The I/O APIC ID.
+    pub id: u8,
+
+    /// The physical address of the registers.
+    pub address: PhysicalAddress,
+
+    /// The first global system interrupt handled by the I/O APIC.
+    pub gsi_base: u32,
+}
+
+/// The `MadtInterruptOverride` type.
+///
+/// Represents an ISA IRQ that isn't identity mapped
+/// to a global system interrupt.
+#[derive(Debug, Copy, Clone)]
+pub struct MadtInterruptOverride {
+    /// The ISA IRQ.
+    pub irq: u8,
+
+    /// The global system interrupt.
+    pub gsi: u32,
+
+    /// The polarity and trigger mode flags.
+    pub flags: u16,
+}
+
+/// The `Madt` type.
+///
+/// Holds the parsed multiple APIC description table.
+#[derive(Debug)]
+pub struct Madt {
+    /// The physical address of the local APICs.
+    pub local_apic_address: PhysicalAddress,
+
+    /// The processors.
+    pub local_apics: Vec<MadtLocalApic>,
+
+    /// The I/O APICs.
+    pub io_apics: Vec<MadtIoApic>,
+
+    /// The interrupt source overrides.
+    pub overrides: Vec<MadtInterruptOverride>,
+}
+
+/// Maps a physical memory region and returns its virtual address.
+fn map(addr: PhysicalAddress, size: usize) -> VirtualAddress {
+    MEMORY_CONTROLLER.lock()
+        .as_mut()
+        .expect("The memory management is not initialized")
+        .map_physical_region(addr, size, EntryFlags::empty())
+}
+
+/// Tests if the bytes of a mapped region sum up to zero.
+fn is_checksum_valid(addr: VirtualAddress, size: usize) -> bool {
+    let mut sum: u8 = 0;
+    for offset in 0..size {
+        sum = sum.wrapping_add(unsafe { *((addr + offset) as *const u8) });
+    }
+    sum == 0
+}
+
+/// Reads a little endian value of up to eight bytes.
+///
+/// Table fields don't have to be aligned, so they're read byte by byte.
+fn read_le(addr: VirtualAddress, size: usize) -> u64 {
+    let mut value = 0;
+    for i in 0..size {
+        let byte = unsafe { *((addr + i) as *const u8) };
+        value |= (byte as u64) << (i * 8);
+    }
+    value
+}
+
+/// Searches the BIOS memory areas for the root system description pointer.
+fn find_rsdp() -> Option<&'static Rsdp> {
+    // Get the extended BIOS data area
+    let ebda = unsafe { *(map(0x40e, 2) as *const u16) } as PhysicalAddress * 16;
+    let areas = [(ebda, 1024), (0xe0000, 0x20000)];
+
+    // The RSDP is aligned to 16 bytes, only the ACPI 1.0 part
+    // of 20 bytes has to fit into the area
+    for &(start, size) in areas.iter() {
+        if start == 0 {
+            continue;
+        }
+        let virt = map(start, size);
+        let mut offset = 0;
+        while offset + 20 <= size {
+            let rsdp = unsafe { &*((virt + offset) as *const Rsdp) };
+            if &rsdp.signature == b"RSD PTR " && is_checksum_valid(virt + offset, 20) {
+                return Some(rsdp);
+            }
+            offset += 16;
+        }
+    }
+    None
+}
+
+/// Maps a system description table and validates it.
+fn map_table(addr: PhysicalAddress) -> Option<&'static SdtHeader> {
+    let header = unsafe { &*(map(addr, size_of::<SdtHeader>()) as *const SdtHeader) };
+    let length = header.length as usize;
+    let virt = map(addr, length);
+    if is_checksum_valid(virt, length) {
+        Some(header)
+    } else {
+        None
+    }
+}
+
+/// Finds the system description table with the specified signature.
+pub fn find_table(signature: &[u8; 4]) -> Option<&'static SdtHeader> {
+    let rsdp = match find_rsdp() {
+        Some(rsdp) => rsdp,
+        None => return None,
+    };
+
+    // Prefer the XSDT, which holds 64-bit table addresses
+    let (root, entry_size) = if rsdp.revision >= 2 && rsdp.xsdt_address != 0 {
+        (rsdp.xsdt_address as PhysicalAddress, 8)
+    } else {
+        (rsdp.rsdt_address as PhysicalAddress, 4)
+    };
+    let root = match map_table(root) {
+        Some(root) => root,
+        None => return None,
+    };
+
+    // Walk the table addresses behind the root table header
+    let entries = root as *const _ as usize + size_of::<SdtHeader>();
+    let count = (root.length as usize - size_of::<SdtHeader>()) / entry_size;
+    for i in 0..count {
+        let addr = read_le(entries + i * entry_size, entry_size) as PhysicalAddress;
+        if let Some(table) = map_table(addr) {
+            if &table.signature == signature {
+                return Some(table);
+            }
+        }
+    }
+    None
+}
+
+/// Finds and parses the multiple APIC description table.
+pub fn parse_madt() -> Option<Madt> {
+    let table = match find_table(b"APIC") {
+        Some(table) => table,
+        None => return None,
+    };
+    let start = table as *const _ as usize;
+    let end = start + table.length as usize;
+    let read_u8 = |addr: usize| unsafe { *(addr as *const u8) };
+    let read_u16 = |addr: usize| read_le(addr, 2) as u16;
+    let read_u32 = |addr: usize| read_le(addr, 4) as u32;
+    let read_u64 = |addr: usize| read_le(addr, 8);
+
+    // The local APIC address and the flags follow the header
+    let mut madt = Madt {
+        local_apic_address: read_u32(start + size_of::<SdtHeader>()) as PhysicalAddress,
+        local_apics: Vec::new(),
+        io_apics: Vec::new(),
+        overrides: Vec::new(),
+    };
+
+    // Walk the variable length entries
+    let mut entry = start + size_of::<SdtHeader>() + 8;
+    while entry + 2 <= end {
+        let entry_type = read_u8(entry);
+        let entry_length = read_u8(entry + 1) as usize;
+        if entry_length < 2 {
+            break;
+        }
+        match entry_type {
+            0 => {
+                madt.local_apics.push(MadtLocalApic {
+                    processor_id: read_u8(entry + 2),
+                    apic_id: read_u8(entry + 3),
+                    enabled: read_u32(entry + 4) & 1 != 0,
+                })
+            }
+            1 => {
+                madt.io_apics.push(MadtIoApic {
+                    id: read_u8(entry + 2),
+                    address: read_u32(entry + 4) as PhysicalAddress,
+                    gsi_base: read_u32(entry + 8),
+                })
+            }
+            2 => {
+                madt.overrides.push(MadtInterruptOverride {
+                    irq: read_u8(entry + 3),
+                    gsi: read_u32(entry + 4),
+                    flags: read_u16(entry + 8),
+                })
+            }
+            5 => madt.local_apic_address = read_u64(entry + 4) as PhysicalAddress,
+            _ => (),
+        }
+        entry += entry_length;
+    }
+    Some(madt)
+}
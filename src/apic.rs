@@ -0,0 +1,291 @@
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicBool, Ordering};
+use collections::Vec;
+use spin::Mutex;
+use acpi::{self, MadtInterruptOverride};
+use cpu;
+use interrupts::{self, InterruptFrame};
+use memory::MEMORY_CONTROLLER;
+use memory::paging::{PhysicalAddress, VirtualAddress, WRITABLE, NO_CACHE};
+use pic;
+
+/// The IA32_APIC_BASE model specific register.
+const IA32_APIC_BASE: u32 = 0x1b;
+
+/// The global enable flag of the IA32_APIC_BASE register.
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+
+/// The vector of spurious local APIC interrupts.
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+
+/// The task priority register.
+const LAPIC_TPR: usize = 0x80;
+
+/// The end of interrupt register.
+const LAPIC_EOI: usize = 0xb0;
+
+/// The spurious interrupt vector register.
+const LAPIC_SVR: usize = 0xf0;
+
+/// The ID register.
+const LAPIC_ID: usize = 0x20;
+
+/// The software enable flag of the spurious interrupt vector register.
+const SVR_ENABLE: u32 = 1 << 8;
+
+/// The redirection entry mask flag.
+const REDIRECTION_MASKED: u64 = 1 << 16;
+
+/// The redirection entry active low polarity flag.
+const REDIRECTION_ACTIVE_LOW: u64 = 1 << 13;
+
+/// The redirection entry level triggered flag.
+const REDIRECTION_LEVEL_TRIGGERED: u64 = 1 << 15;
+
+/// Whether interrupts are routed through the APICs instead of the PIC.
+static APIC_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The local APIC of the boot processor.
+static LOCAL_APIC: Mutex<Option<LocalApic>> = Mutex::new(None);
+
+/// The I/O APICs and the ISA interrupt source overrides.
+static IO_APICS: Mutex<Option<IoApics>> = Mutex::new(None);
+
+/// The `LocalApic` type.
+pub struct LocalApic {
+    /// The virtual address of the registers.
+    base: VirtualAddress,
+}
+
+/// The `LocalApic` implementation.
+impl LocalApic {
+    /// Reads a register.
+    fn read(&self, register: usize) -> u32 {
+        unsafe { read_volatile((self.base + register) as *const u32) }
+    }
+
+    /// Writes a register.
+    fn write(&mut self, register: usize, value: u32) {
+        unsafe { write_volatile((self.base + register) as *mut u32, value) }
+    }
+
+    /// Enables the local APIC and accepts interrupts of every priority.
+    fn enable(&mut self) {
+        self.write(LAPIC_SVR, SVR_ENABLE | SPURIOUS_VECTOR as u32);
+        self.write(LAPIC_TPR, 0);
+    }
+
+    /// Gets the ID.
+    pub fn id(&self) -> u8 {
+        (self.read(LAPIC_ID) >> 24) as u8
+    }
+
+    /// Signals the end of the interrupt that is currently being serviced.
+    pub fn send_eoi(&mut self) {
+        self.write(LAPIC_EOI, 0);
+    }
+}
+
+/// The `IoApic` type.
+pub struct IoApic {
+    /// The virtual address of the registers.
+    base: VirtualAddress,
+
+    /// The first global system interrupt.
+    gsi_base: u32,
+
+    /// The number of redirection entries.
+    entry_count: u32,
+}
+
+/// The `IoApic` implementation.
+impl IoApic {
+    /// Constructs a new `IoApic` and masks every redirection entry.
+    fn new(base: VirtualAddress, gsi_base: u32) -> IoApic {
+        let mut io_apic = IoApic {
+            base: base,
+            gsi_base: gsi_base,
+            entry_count: 0,
+        };
+        io_apic.entry_count = (io_apic.read(1) >> 16 & 0xff) + 1;
+        for entry in 0..io_apic.entry_count {
+            io_apic.write_redirection(entry, REDIRECTION_MASKED);
+        }
+        io_apic
+    }
+
+    /// Reads a register through the IOREGSEL and IOWIN registers.
+    fn read(&self, register: u32) -> u32 {
+        unsafe {
+            write_volatile(self.base as *mut u32, register);
+            read_volatile((self.base + 0x10) as *const u32)
+        }
+    }
+
+    /// Writes a register through the IOREGSEL and IOWIN registers.
+    fn write(&mut self, register: u32, value: u32) {
+        unsafe {
+            write_volatile(self.base as *mut u32, register);
+            write_volatile((self.base + 0x10) as *mut u32, value);
+        }
+    }
+
+    /// Reads a redirection entry.
+    fn read_redirection(&self, entry: u32) -> u64 {
+        let low = self.read(0x10 + entry * 2) as u64;
+        let high = self.read(0x10 + entry * 2 + 1) as u64;
+        high << 32 | low
+    }
+
+    /// Writes a redirection entry.
+    fn write_redirection(&mut self, entry: u32, value: u64) {
+        // Mask the entry while it's half written
+        self.write(0x10 + entry * 2, REDIRECTION_MASKED as u32);
+        self.write(0x10 + entry * 2 + 1, (value >> 32) as u32);
+        self.write(0x10 + entry * 2, value as u32);
+    }
+
+    /// Tests if the I/O APIC handles the specified global system interrupt.
+    fn handles(&self, gsi: u32) -> bool {
+        gsi >= self.gsi_base && gsi < self.gsi_base + self.entry_count
+    }
+}
+
+/// The `IoApics` type.
+struct IoApics {
+    /// The I/O APICs.
+    io_apics: Vec<IoApic>,
+
+    /// The ISA interrupt source overrides.
+    overrides: Vec<MadtInterruptOverride>,
+}
+
+/// The `IoApics` implementation.
+impl IoApics {
+    /// Gets the global system interrupt and the redirection flags of an ISA IRQ.
+    fn resolve(&self, irq: u8) -> (u32, u64) {
+        match self.overrides.iter().find(|o| o.irq == irq) {
+            Some(o) => {
+                let mut flags = 0;
+                // Polarity: 0b11 is active low
+                if o.flags & 0b11 == 0b11 {
+                    flags |= REDIRECTION_ACTIVE_LOW;
+                }
+                // Trigger mode: 0b11 is level triggered
+                if o.flags >> 2 & 0b11 == 0b11 {
+                    flags |= REDIRECTION_LEVEL_TRIGGERED;
+                }
+                (o.gsi, flags)
+            }
+            None => (irq as u32, 0),
+        }
+    }
+
+    /// Gets the I/O APIC and the redirection entry of a global system interrupt.
+    fn find(&mut self, gsi: u32) -> Option<(&mut IoApic, u32)> {
+        self.io_apics
+            .iter_mut()
+            .find(|io_apic| io_apic.handles(gsi))
+            .map(|io_apic| {
+                let entry = gsi - io_apic.gsi_base;
+                (io_apic, entry)
+            })
+    }
+}
+
+/// Maps a memory mapped register page as uncacheable.
+fn map_registers(addr: PhysicalAddress) -> VirtualAddress {
+    MEMORY_CONTROLLER.lock()
+        .as_mut()
+        .expect("The memory management is not initialized")
+        .map_physical_region(addr, 4096, WRITABLE | NO_CACHE)
+}
+
+/// Tests if the CPU has a local APIC.
+pub fn is_supported() -> bool {
+    cpu::cpuid(1).edx & 1 << 9 != 0
+}
+
+/// Tests if interrupts are routed through the APICs.
+pub fn is_enabled() -> bool {
+    APIC_ENABLED.load(Ordering::SeqCst)
+}
+
+/// Initializes the local APIC and the I/O APICs.
+///
+/// Disables the legacy PIC on success. The PIC stays in charge if the CPU
+/// has no local APIC or the firmware has no MADT describing the I/O APICs.
+pub fn init() -> bool {
+    if !is_supported() {
+        return false;
+    }
+    let madt = match acpi::parse_madt() {
+        Some(madt) => madt,
+        None => return false,
+    };
+    if madt.io_apics.is_empty() {
+        return false;
+    }
+
+    // Enable the local APIC
+    let apic_base = unsafe { cpu::read_msr(IA32_APIC_BASE) };
+    unsafe {
+        cpu::write_msr(IA32_APIC_BASE, apic_base | APIC_BASE_ENABLE);
+    }
+    let mut local_apic = LocalApic { base: map_registers(madt.local_apic_address) };
+    local_apic.enable();
+    interrupts::register_handler(SPURIOUS_VECTOR, spurious_interrupt_handler);
+
+    // Mask the legacy PIC, its vectors are reused by the I/O APICs
+    pic::PICS.lock().disable();
+
+    let io_apics = madt.io_apics
+        .iter()
+        .map(|io_apic| IoApic::new(map_registers(io_apic.address), io_apic.gsi_base))
+        .collect();
+    *LOCAL_APIC.lock() = Some(local_apic);
+    *IO_APICS.lock() = Some(IoApics {
+        io_apics: io_apics,
+        overrides: madt.overrides,
+    });
+    APIC_ENABLED.store(true, Ordering::SeqCst);
+    true
+}
+
+/// Gets the ID of the local APIC of the boot processor.
+pub fn local_apic_id() -> u8 {
+    LOCAL_APIC.lock().as_ref().expect("The local APIC is not enabled").id()
+}
+
+/// Routes an ISA IRQ to the specified vector on the CPU with the specified APIC ID.
+///
+/// Applies the interrupt source overrides of the MADT and unmasks the IRQ.
+pub fn route_irq(irq: u8, vector: u8, apic_id: u8) {
+    let mut io_apics = IO_APICS.lock();
+    let io_apics = io_apics.as_mut().expect("The I/O APICs are not enabled");
+    let (gsi, flags) = io_apics.resolve(irq);
+    let (io_apic, entry) = io_apics.find(gsi)
+        .expect("No I/O APIC handles the global system interrupt");
+    io_apic.write_redirection(entry, (apic_id as u64) << 56 | flags | vector as u64);
+}
+
+/// Masks an ISA IRQ.
+pub fn mask_irq(irq: u8) {
+    let mut io_apics = IO_APICS.lock();
+    let io_apics = io_apics.as_mut().expect("The I/O APICs are not enabled");
+    let (gsi, _) = io_apics.resolve(irq);
+    if let Some((io_apic, entry)) = io_apics.find(gsi) {
+        let redirection = io_apic.read_redirection(entry);
+        io_apic.write_redirection(entry, redirection | REDIRECTION_MASKED);
+    }
+}
+
+/// Signals the end of the interrupt that is currently being serviced.
+pub fn send_eoi() {
+    LOCAL_APIC.lock().as_mut().expect("The local APIC is not enabled").send_eoi();
+}
+
+/// Handles a spurious local APIC interrupt.
+///
+/// Spurious interrupts must not be acknowledged.
+fn spurious_interrupt_handler(_frame: &mut InterruptFrame) {}
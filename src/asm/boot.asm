@@ -1,12 +1,22 @@
 global real_mode_start
+global stack_guard_page
 extern kmain_setup
 extern kmain
 
+; The virtual address the kernel is linked at
+; Must match KERNEL_OFFSET in linker.ld and memory/mod.rs
+KERNEL_OFFSET equ 0xFFFFFFFF80000000
+
+; The P4 index of the recursive mapping
+; Must match RECURSIVE_INDEX in memory/paging/mod.rs
+RECURSIVE_INDEX equ 510
+
 %include "paging.asm"
 %include "gdt.asm"
 
 ; Real mode text section
-section .text
+; Linked at its physical address, since paging is still off
+section .boot.text progbits alloc exec nowrite
 bits 32
 
 ; Real mode entry point
@@ -16,7 +26,7 @@ real_mode_start:
   cli
 
   ; Point stack pointer to stack
-  mov esp, stack_top
+  mov esp, stack_top - KERNEL_OFFSET
 
   ; Load the multiboot info pointer
   mov edi, ebx
@@ -25,16 +35,25 @@ real_mode_start:
   call setup_paging
 
   ; Map the P4 table
-  mov eax, page_map_level4_table
+  mov eax, page_map_level4_table - KERNEL_OFFSET
   or eax, 0b11
-  mov [page_map_level4_table + 511 * 8], eax
+  mov [page_map_level4_table - KERNEL_OFFSET + RECURSIVE_INDEX * 8], eax
 
   ; Load the global descriptor table
-  lgdt [gdt64.pointer]
+  lgdt [gdt64.boot_pointer - KERNEL_OFFSET]
 
   ; Jump into long mode
   jmp enter_long_mode
 
+; Long mode trampoline
+; Still runs at its physical address
+bits 64
+long_mode_trampoline:
+
+  ; Jump into the higher half
+  mov rax, long_mode_start
+  jmp rax
+
 ; Long mode text section
 section .text
 bits 64
@@ -42,6 +61,13 @@ bits 64
 ; Long mode entry point
 long_mode_start:
 
+  ; Move the stack pointer into the higher half
+  mov rax, KERNEL_OFFSET
+  add rsp, rax
+
+  ; Reload the global descriptor table from the higher half
+  lgdt [gdt64.pointer]
+
   ; Call the kernel setup
   call kmain_setup
 
@@ -55,7 +81,13 @@ long_mode_start:
 section .bss
 align 4096
 
+; Bootstrap stack guard page
+; Unmapped once the kernel is remapped, so that
+; a stack overflow faults instead of corrupting memory
+stack_guard_page:
+  resb 4096
+
 ; Bootstrap stack
 stack_bottom:
-  resb 4096
+  resb 4096 * 4
 stack_top:
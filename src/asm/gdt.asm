@@ -1,4 +1,4 @@
-section .text
+section .boot.text progbits alloc exec nowrite
 bits 32
 
 ; Set the GDT up
@@ -11,7 +11,7 @@ enter_long_mode:
   mov es, ax
 
   ; Far jump into long mode
-  jmp gdt64.code:long_mode_start
+  jmp gdt64.code:long_mode_trampoline
 
 section .rodata
 
@@ -45,7 +45,15 @@ gdt64:
       (1 << GDT_BIT_PRESENT)  |\
       (1 << GDT_BIT_READWRITE)
 
+  ; End of the descriptors
+  .end:
+
   ; GDT pointer
   .pointer:
-    dw .pointer - gdt64 - 1
+    dw .end - gdt64 - 1
     dq gdt64
+
+  ; GDT pointer used before paging is enabled
+  .boot_pointer:
+    dw .end - gdt64 - 1
+    dq gdt64 - KERNEL_OFFSET
@@ -0,0 +1,118 @@
+global isr_stub_table
+extern interrupt_dispatch
+
+section .text
+bits 64
+
+; Defines an interrupt stub that pushes a zero error code
+%macro isr_no_error 1
+isr_stub_%+%1:
+  push 0
+  push %1
+  jmp isr_common
+%endmacro
+
+; Defines an interrupt stub for exceptions that push an error code
+%macro isr_error 1
+isr_stub_%+%1:
+  push %1
+  jmp isr_common
+%endmacro
+
+; Common interrupt entry
+; Saves the registers and passes them to the Rust dispatcher
+isr_common:
+
+  ; Save the general purpose registers
+  push rax
+  push rbx
+  push rcx
+  push rdx
+  push rsi
+  push rdi
+  push rbp
+  push r8
+  push r9
+  push r10
+  push r11
+  push r12
+  push r13
+  push r14
+  push r15
+
+  ; Call the dispatcher with a pointer to the interrupt frame
+  mov rdi, rsp
+  cld
+  call interrupt_dispatch
+
+  ; Restore the general purpose registers
+  pop r15
+  pop r14
+  pop r13
+  pop r12
+  pop r11
+  pop r10
+  pop r9
+  pop r8
+  pop rbp
+  pop rdi
+  pop rsi
+  pop rdx
+  pop rcx
+  pop rbx
+  pop rax
+
+  ; Drop the vector number and the error code
+  add rsp, 16
+  iretq
+
+; CPU exceptions
+isr_no_error 0
+isr_no_error 1
+isr_no_error 2
+isr_no_error 3
+isr_no_error 4
+isr_no_error 5
+isr_no_error 6
+isr_no_error 7
+isr_error    8
+isr_no_error 9
+isr_error    10
+isr_error    11
+isr_error    12
+isr_error    13
+isr_error    14
+isr_no_error 15
+isr_no_error 16
+isr_error    17
+isr_no_error 18
+isr_no_error 19
+isr_no_error 20
+isr_error    21
+isr_no_error 22
+isr_no_error 23
+isr_no_error 24
+isr_no_error 25
+isr_no_error 26
+isr_no_error 27
+isr_no_error 28
+isr_error    29
+isr_error    30
+isr_no_error 31
+
+; Hardware and software interrupts
+%assign vector 32
+%rep 224
+  isr_no_error vector
+  %assign vector vector + 1
+%endrep
+
+section .rodata
+
+; The addresses of all interrupt stubs
+isr_stub_table:
+%assign vector 0
+%rep 256
+  dq isr_stub_%+vector
+  %assign vector vector + 1
+%endrep
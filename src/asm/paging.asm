@@ -1,25 +1,34 @@
-section .text
+section .boot.text progbits alloc exec nowrite
 bits 32
 
 ; Set paging up
+; Identity maps the first GiB and maps it again at KERNEL_OFFSET
 setup_paging:
 
   ; Point PML4 to PDP
-  mov eax, page_directory_pointer_table
+  mov eax, page_directory_pointer_table - KERNEL_OFFSET
   or eax, 0b11
-  mov dword [page_map_level4_table], eax
+  mov dword [page_map_level4_table - KERNEL_OFFSET], eax
+
+  ; Point the last PML4 entry to the higher half PDP
+  mov eax, page_directory_pointer_table_high - KERNEL_OFFSET
+  or eax, 0b11
+  mov dword [page_map_level4_table - KERNEL_OFFSET + 511 * 8], eax
 
   ; Point PDP to PD
-  mov eax, page_directory_table
+  mov eax, page_directory_table - KERNEL_OFFSET
   or eax, 0b11
-  mov dword [page_directory_pointer_table], eax
+  mov dword [page_directory_pointer_table - KERNEL_OFFSET], eax
+
+  ; Point the higher half PDP entry of KERNEL_OFFSET to the same PD
+  mov dword [page_directory_pointer_table_high - KERNEL_OFFSET + 510 * 8], eax
 
   ; Initialize counter
   mov ecx, 0
   call .map_page_directory_table
 
   ; Move page table to cr3
-  mov eax, page_map_level4_table
+  mov eax, page_map_level4_table - KERNEL_OFFSET
   mov cr3, eax
 
   ; Enable physical address extension
@@ -45,7 +54,7 @@ setup_paging:
     mov eax, 0x200000
     mul ecx
     or eax, 0b10000011
-    mov [page_directory_table + ecx * 8], eax
+    mov [page_directory_table - KERNEL_OFFSET + ecx * 8], eax
     inc ecx
     cmp ecx, 512
     jne .map_page_directory_table
@@ -58,5 +67,7 @@ page_map_level4_table:
   resb 4096
 page_directory_pointer_table:
   resb 4096
+page_directory_pointer_table_high:
+  resb 4096
 page_directory_table:
   resb 4096
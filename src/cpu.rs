@@ -0,0 +1,39 @@
+/// The `CpuidResult` type.
+#[derive(Debug, Copy, Clone)]
+pub struct CpuidResult {
+    pub eax: u32,
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+}
+
+/// Executes the CPUID instruction for the specified leaf.
+pub fn cpuid(leaf: u32) -> CpuidResult {
+    let (eax, ebx, ecx, edx): (u32, u32, u32, u32);
+    unsafe {
+        asm!("cpuid"
+             : "={eax}" (eax), "={ebx}" (ebx), "={ecx}" (ecx), "={edx}" (edx)
+             : "{eax}" (leaf), "{ecx}" (0)
+             :: "volatile");
+    }
+    CpuidResult {
+        eax: eax,
+        ebx: ebx,
+        ecx: ecx,
+        edx: edx,
+    }
+}
+
+/// Reads a model specific register.
+pub unsafe fn read_msr(msr: u32) -> u64 {
+    let (high, low): (u32, u32);
+    asm!("rdmsr" : "={eax}" (low), "={edx}" (high) : "{ecx}" (msr) :: "volatile");
+    (high as u64) << 32 | low as u64
+}
+
+/// Writes a model specific register.
+pub unsafe fn write_msr(msr: u32, value: u64) {
+    let low = value as u32;
+    let high = (value >> 32) as u32;
+    asm!("wrmsr" :: "{ecx}" (msr), "{eax}" (low), "{edx}" (high) : "memory" : "volatile");
+}
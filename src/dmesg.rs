@@ -0,0 +1,47 @@
+use core::fmt::{self, Write};
+use spin::Mutex;
+use interrupts;
+use log::LogBuffer;
+use serial::SerialWriter;
+
+/// The kernel message buffer.
+///
+/// Records everything printed to the console and every log record.
+pub static DMESG: Mutex<LogBuffer> = Mutex::new(LogBuffer::new());
+
+/// Records printed output.
+///
+/// Used by `print!` and `println!`.
+pub fn record(args: fmt::Arguments) {
+    interrupts::without_interrupts(|| {
+        let _ = DMESG.lock().write_fmt(args);
+    });
+}
+
+/// Copies kernel messages starting at an absolute position into a buffer.
+///
+/// Returns the number of copied bytes and the position to continue at.
+/// See `LogBuffer::read_at`.
+pub fn read(position: usize, buffer: &mut [u8]) -> (usize, usize) {
+    interrupts::without_interrupts(|| DMESG.lock().read_at(position, buffer))
+}
+
+/// Gets the position behind the newest kernel message.
+pub fn position() -> usize {
+    interrupts::without_interrupts(|| DMESG.lock().written())
+}
+
+/// Writes every kernel message to a serial port.
+///
+/// Gives up if the kernel message buffer is locked, since this
+/// is called on panic, when the lock holder may never return.
+pub fn dump(writer: &SerialWriter) {
+    if let Some(dmesg) = DMESG.try_lock() {
+        let (older, newer) = dmesg.contents();
+        for &byte in older.iter().chain(newer.iter()) {
+            if writer.write_byte(byte).is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use core::fmt::{self, Write};
+use vga::{self, Console, Color, HalfColor};
+use serial::COM1;
+use memory;
+use super::InterruptFrame;
+
+/// The names of the CPU exceptions.
+static EXCEPTION_NAMES: [&'static str; 32] = ["Divide Error",
+                                              "Debug",
+                                              "Non-Maskable Interrupt",
+                                              "Breakpoint",
+                                              "Overflow",
+                                              "Bound Range Exceeded",
+                                              "Invalid Opcode",
+                                              "Device Not Available",
+                                              "Double Fault",
+                                              "Coprocessor Segment Overrun",
+                                              "Invalid TSS",
+                                              "Segment Not Present",
+                                              "Stack-Segment Fault",
+                                              "General Protection Fault",
+                                              "Page Fault",
+                                              "Reserved",
+                                              "x87 Floating-Point Exception",
+                                              "Alignment Check",
+                                              "Machine Check",
+                                              "SIMD Floating-Point Exception",
+                                              "Virtualization Exception",
+                                              "Control Protection Exception",
+                                              "Reserved",
+                                              "Reserved",
+                                              "Reserved",
+                                              "Reserved",
+                                              "Reserved",
+                                              "Reserved",
+                                              "Hypervisor Injection Exception",
+                                              "VMM Communication Exception",
+                                              "Security Exception",
+                                              "Reserved"];
+
+/// Gets the name of a CPU exception.
+pub fn exception_name(vector: u64) -> &'static str {
+    EXCEPTION_NAMES.get(vector as usize).map_or("Unknown", |name| *name)
+}
+
+/// Reads the CR2 register.
+///
+/// Holds the faulting address after a page fault.
+pub fn read_cr2() -> u64 {
+    let cr2: u64;
+    unsafe {
+        asm!("mov %cr2, $0" : "=r" (cr2));
+    }
+    cr2
+}
+
+/// Prints to the VGA console and to COM1.
+///
+/// A missing COM1 is ignored. Either is skipped if the interrupted
+/// code holds its lock, since waiting for it would never end.
+pub fn print_report(args: fmt::Arguments) {
+    if let Some(mut console) = Console.try_lock() {
+        let _ = console.write_fmt(args);
+    }
+    if let Some(mut com1) = COM1.try_lock() {
+        let _ = com1.write_fmt(args);
+    }
+}
+
+/// Prints the state of the CPU at the time of an exception.
+///
+/// Switches to the first virtual console, which the report is printed to.
+pub fn report_exception(frame: &InterruptFrame) {
+    vga::switch_console(0);
+    if let Some(mut console) = Console.try_lock() {
+        console.set_color(Color::new(HalfColor::LightRed, HalfColor::Black));
+    }
+    print_report(format_args!("\n***\tEXCEPTION: {} (#{})\n",
+                              exception_name(frame.vector),
+                              frame.vector));
+    print_report(format_args!("\tError code: 0x{:x}\n\tRIP: 0x{:016x}\n\tCS: 0x{:x}\n",
+                              frame.error_code,
+                              frame.rip,
+                              frame.cs));
+    print_report(format_args!("\tRFLAGS: 0x{:016x}\n\tFaulting address (CR2): 0x{:016x}\n",
+                              frame.rflags,
+                              read_cr2()));
+}
+
+/// Handles a CPU exception that has no registered handler.
+///
+/// Reports the exception and halts the CPU.
+pub fn unhandled_exception(frame: &mut InterruptFrame) -> ! {
+    report_exception(frame);
+    loop {
+        unsafe {
+            asm!("cli; hlt" :::: "volatile");
+        }
+    }
+}
+
+/// Handles a double fault.
+///
+/// Runs on its own stack, so that kernel stack
+/// overflows are reported instead of triple faulting.
+pub fn double_fault_handler(frame: &mut InterruptFrame) {
+    report_exception(frame);
+    if memory::is_stack_guard_page(read_cr2() as usize) {
+        print_report(format_args!("\tThe faulting address is in a stack guard page, \
+                                   the stack probably overflowed\n"));
+    }
+    panic!("Double fault at 0x{:x}", frame.rip);
+}
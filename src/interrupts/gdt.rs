@@ -0,0 +1,177 @@
+use core::mem::size_of;
+use super::idt::DescriptorTablePointer;
+
+/// The number of GDT entries.
+const GDT_ENTRY_COUNT: usize = 8;
+
+/// The IST index of the double fault stack.
+pub const DOUBLE_FAULT_IST_INDEX: usize = 0;
+
+// The segment descriptor bit flags
+bitflags! {
+    flags DescriptorFlags: u64 {
+        const READ_WRITE =   1 << 41,
+        const CONFORMING =   1 << 42,
+        const EXECUTABLE =   1 << 43,
+        const USER_SEGMENT = 1 << 44,
+        const PRESENT =      1 << 47,
+        const LONG_MODE =    1 << 53,
+    }
+}
+
+/// The `TaskStateSegment` type.
+///
+/// Holds the interrupt stack table in long mode.
+#[repr(C, packed)]
+pub struct TaskStateSegment {
+    /// Reserved.
+    reserved_1: u32,
+
+    /// The stacks used when switching to a more privileged level.
+    pub privilege_stack_table: [u64; 3],
+
+    /// Reserved.
+    reserved_2: u64,
+
+    /// The interrupt stack table.
+    pub interrupt_stack_table: [u64; 7],
+
+    /// Reserved.
+    reserved_3: u64,
+
+    /// Reserved.
+    reserved_4: u16,
+
+    /// The offset of the I/O permission bitmap.
+    pub iomap_base: u16,
+}
+
+/// The `TaskStateSegment` implementation.
+impl TaskStateSegment {
+    /// Constructs a new empty `TaskStateSegment`.
+    ///
+    /// The I/O permission bitmap offset has to be set to the size
+    /// of the segment before it's loaded, to disable the bitmap.
+    pub const fn new() -> TaskStateSegment {
+        TaskStateSegment {
+            reserved_1: 0,
+            privilege_stack_table: [0; 3],
+            reserved_2: 0,
+            interrupt_stack_table: [0; 7],
+            reserved_3: 0,
+            reserved_4: 0,
+            iomap_base: 0,
+        }
+    }
+}
+
+/// The `Descriptor` type.
+pub enum Descriptor {
+    /// A code or data segment descriptor.
+    UserSegment(u64),
+
+    /// A system segment descriptor, which takes up two entries.
+    SystemSegment(u64, u64),
+}
+
+/// The `Descriptor` implementation.
+impl Descriptor {
+    /// Constructs a new kernel code segment descriptor.
+    pub fn kernel_code_segment() -> Descriptor {
+        let flags = USER_SEGMENT | PRESENT | READ_WRITE | EXECUTABLE | LONG_MODE;
+        Descriptor::UserSegment(flags.bits())
+    }
+
+    /// Constructs a new kernel data segment descriptor.
+    pub fn kernel_data_segment() -> Descriptor {
+        let flags = USER_SEGMENT | PRESENT | READ_WRITE;
+        Descriptor::UserSegment(flags.bits())
+    }
+
+    /// Constructs a new task state segment descriptor.
+    pub fn tss_segment(tss: &'static TaskStateSegment) -> Descriptor {
+        let base = tss as *const _ as u64;
+        let limit = (size_of::<TaskStateSegment>() - 1) as u64;
+
+        // Available 64-bit TSS
+        let mut low = PRESENT.bits() | 0b1001 << 40;
+        low |= limit & 0xffff;
+        low |= ((limit >> 16) & 0xf) << 48;
+        low |= (base & 0xffffff) << 16;
+        low |= ((base >> 24) & 0xff) << 56;
+        let high = base >> 32;
+        Descriptor::SystemSegment(low, high)
+    }
+}
+
+/// The `Gdt` type.
+///
+/// Represents the global descriptor table.
+pub struct Gdt {
+    /// The entries.
+    table: [u64; GDT_ENTRY_COUNT],
+
+    /// The index of the next free entry.
+    next_free: usize,
+}
+
+/// The `Gdt` implementation.
+impl Gdt {
+    /// Constructs a new `Gdt` with only the null descriptor.
+    pub const fn new() -> Gdt {
+        Gdt {
+            table: [0; GDT_ENTRY_COUNT],
+            next_free: 1,
+        }
+    }
+
+    /// Adds a descriptor and returns its segment selector.
+    pub fn add_entry(&mut self, entry: Descriptor) -> u16 {
+        let index = match entry {
+            Descriptor::UserSegment(value) => self.push(value),
+            Descriptor::SystemSegment(low, high) => {
+                let index = self.push(low);
+                self.push(high);
+                index
+            }
+        };
+        (index * 8) as u16
+    }
+
+    /// Pushes a raw entry.
+    fn push(&mut self, value: u64) -> usize {
+        assert!(self.next_free < GDT_ENTRY_COUNT, "The GDT is full");
+        let index = self.next_free;
+        self.table[index] = value;
+        self.next_free += 1;
+        index
+    }
+
+    /// Loads the table into the GDTR register.
+    ///
+    /// The segment registers have to be reloaded afterwards.
+    pub unsafe fn load(&'static self) {
+        let pointer = DescriptorTablePointer {
+            limit: (self.table.len() * size_of::<u64>() - 1) as u16,
+            base: self.table.as_ptr() as u64,
+        };
+        asm!("lgdt ($0)" :: "r" (&pointer) : "memory");
+    }
+}
+
+/// Reloads the code segment register.
+pub unsafe fn set_cs(selector: u16) {
+    asm!("pushq $0; leaq 1f(%rip), %rax; pushq %rax; lretq; 1:"
+         :: "ri" (selector as u64) : "rax" "memory" : "volatile");
+}
+
+/// Reloads the data segment registers.
+pub unsafe fn set_data_segments(selector: u16) {
+    asm!("mov $0, %ds; mov $0, %es; mov $0, %ss"
+         :: "r" (selector) : "memory" : "volatile");
+}
+
+/// Loads the task state segment.
+pub unsafe fn load_tss(selector: u16) {
+    asm!("ltr $0" :: "r" (selector) : "memory" : "volatile");
+}
@@ -0,0 +1,110 @@
+use core::mem::size_of;
+
+/// The number of IDT entries.
+pub const IDT_ENTRY_COUNT: usize = 256;
+
+/// The `IdtEntry` type.
+///
+/// Represents a 64-bit interrupt gate.
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct IdtEntry {
+    /// The lower 16 bits of the handler address.
+    offset_low: u16,
+
+    /// The code segment selector.
+    selector: u16,
+
+    /// The interrupt stack table index and the gate options.
+    options: u16,
+
+    /// The middle 16 bits of the handler address.
+    offset_middle: u16,
+
+    /// The upper 32 bits of the handler address.
+    offset_high: u32,
+
+    /// Reserved.
+    reserved: u32,
+}
+
+/// The `IdtEntry` implementation.
+impl IdtEntry {
+    /// Constructs a new missing `IdtEntry`.
+    pub const fn missing() -> IdtEntry {
+        IdtEntry {
+            offset_low: 0,
+            selector: 0,
+            options: 0,
+            offset_middle: 0,
+            offset_high: 0,
+            reserved: 0,
+        }
+    }
+
+    /// Constructs a new present `IdtEntry`.
+    ///
+    /// Uses an interrupt gate, so interrupts are
+    /// disabled while the handler runs.
+    pub fn new(handler: usize, selector: u16) -> IdtEntry {
+        IdtEntry {
+            offset_low: handler as u16,
+            selector: selector,
+            options: 1 << 15 | 0xe << 8,
+            offset_middle: (handler >> 16) as u16,
+            offset_high: (handler >> 32) as u32,
+            reserved: 0,
+        }
+    }
+}
+
+/// The `DescriptorTablePointer` type.
+#[repr(C, packed)]
+pub struct DescriptorTablePointer {
+    /// The size of the table minus one.
+    pub limit: u16,
+
+    /// The address of the table.
+    pub base: u64,
+}
+
+/// The `Idt` type.
+///
+/// Represents the interrupt descriptor table.
+pub struct Idt {
+    /// The entries.
+    entries: [IdtEntry; IDT_ENTRY_COUNT],
+}
+
+/// The `Idt` implementation.
+impl Idt {
+    /// Constructs a new `Idt` without present entries.
+    pub const fn new() -> Idt {
+        Idt { entries: [IdtEntry::missing(); IDT_ENTRY_COUNT] }
+    }
+
+    /// Sets the handler of the specified vector.
+    pub fn set_handler(&mut self, vector: usize, handler: usize, selector: u16) {
+        self.entries[vector] = IdtEntry::new(handler, selector);
+    }
+
+    /// Sets the interrupt stack table index of the specified vector.
+    ///
+    /// The handler of the vector will always run on that stack.
+    pub fn set_stack_index(&mut self, vector: usize, index: usize) {
+        assert!(index < 7, "Invalid interrupt stack table index {}", index);
+        let options = self.entries[vector].options;
+        self.entries[vector].options = (options & !0b111) | (index as u16 + 1);
+    }
+
+    /// Loads the table into the IDTR register.
+    ///
+    /// The table must stay valid as long as it's loaded.
+    pub unsafe fn load(&'static self) {
+        let pointer = DescriptorTablePointer {
+            limit: (size_of::<Idt>() - 1) as u16,
+            base: self as *const _ as u64,
+        };
+        asm!("lidt ($0)" :: "r" (&pointer) : "memory");
+    }
+}
@@ -0,0 +1,227 @@
+use core::mem::size_of;
+use spin::Mutex;
+use memory::{PAGE_SIZE, MEMORY_CONTROLLER};
+
+mod idt;
+mod gdt;
+mod exceptions;
+mod page_fault;
+use self::idt::{Idt, IDT_ENTRY_COUNT};
+use self::gdt::{Gdt, Descriptor, TaskStateSegment, DOUBLE_FAULT_IST_INDEX};
+
+/// The size of the double fault stack in pages.
+const DOUBLE_FAULT_STACK_PAGES: usize = 4;
+
+/// The `InterruptHandler` type.
+pub type InterruptHandler = fn(&mut InterruptFrame);
+
+/// The interrupt descriptor table.
+static IDT: Mutex<Idt> = Mutex::new(Idt::new());
+
+/// The global descriptor table.
+///
+/// Replaces the GDT set up by `gdt.asm` once `init_gdt` was called.
+static GDT: Mutex<Gdt> = Mutex::new(Gdt::new());
+
+/// The task state segment.
+static TSS: Mutex<TaskStateSegment> = Mutex::new(TaskStateSegment::new());
+
+/// The registered interrupt handlers.
+static HANDLERS: Mutex<[Option<InterruptHandler>; IDT_ENTRY_COUNT]> =
+    Mutex::new([None; IDT_ENTRY_COUNT]);
+
+extern "C" {
+    /// The addresses of the interrupt stubs.
+    ///
+    /// Defined in `interrupts.asm`.
+    static isr_stub_table: [usize; IDT_ENTRY_COUNT];
+}
+
+/// The `InterruptFrame` type.
+///
+/// Represents the state saved by the CPU and the interrupt stubs.
+#[repr(C)]
+#[derive(Debug)]
+pub struct InterruptFrame {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rbp: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64,
+
+    /// The interrupt vector.
+    pub vector: u64,
+
+    /// The error code.
+    ///
+    /// Zero for interrupts without an error code.
+    pub error_code: u64,
+
+    /// The instruction pointer.
+    pub rip: u64,
+
+    /// The code segment.
+    pub cs: u64,
+
+    /// The flags register.
+    pub rflags: u64,
+
+    /// The stack pointer.
+    pub rsp: u64,
+
+    /// The stack segment.
+    pub ss: u64,
+}
+
+/// Initializes the interrupt descriptor table.
+///
+/// Every vector is routed through the interrupt stubs
+/// to `interrupt_dispatch`. Installs the page fault handler.
+pub fn init() {
+    let selector: u16;
+    unsafe {
+        asm!("mov %cs, $0" : "=r" (selector));
+    }
+    let mut idt = IDT.lock();
+    for vector in 0..IDT_ENTRY_COUNT {
+        idt.set_handler(vector, unsafe { isr_stub_table[vector] }, selector);
+    }
+    unsafe {
+        let idt: &'static Idt = &*(&*idt as *const Idt);
+        idt.load();
+    }
+
+    // Register the page fault handler
+    register_handler(14, page_fault::page_fault_handler);
+}
+
+/// Initializes the global descriptor table and the task state segment.
+///
+/// Moves the double fault handler onto its own guarded stack.
+/// Requires the memory management to be initialized.
+pub fn init_gdt() {
+    // Allocate the double fault stack
+    let double_fault_stack = MEMORY_CONTROLLER.lock()
+        .as_mut()
+        .expect("The memory management is not initialized")
+        .alloc_stack(DOUBLE_FAULT_STACK_PAGES)
+        .expect("Could not allocate the double fault stack");
+    debug_assert!(double_fault_stack.top() - double_fault_stack.bottom() ==
+                  DOUBLE_FAULT_STACK_PAGES * PAGE_SIZE);
+
+    // Set the task state segment up
+    let tss: &'static TaskStateSegment = {
+        let mut tss = TSS.lock();
+        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX] = double_fault_stack.top() as u64;
+        // An I/O permission bitmap offset beyond the limit means there is no bitmap
+        tss.iomap_base = size_of::<TaskStateSegment>() as u16;
+        unsafe { &*(&*tss as *const TaskStateSegment) }
+    };
+
+    // Set the global descriptor table up
+    let mut gdt = GDT.lock();
+    let code_selector = gdt.add_entry(Descriptor::kernel_code_segment());
+    let data_selector = gdt.add_entry(Descriptor::kernel_data_segment());
+    let tss_selector = gdt.add_entry(Descriptor::tss_segment(tss));
+    unsafe {
+        let gdt: &'static Gdt = &*(&*gdt as *const Gdt);
+        gdt.load();
+        gdt::set_cs(code_selector);
+        gdt::set_data_segments(data_selector);
+        gdt::load_tss(tss_selector);
+    }
+
+    // Point every gate at the new code segment and
+    // run the double fault handler on its own stack
+    let mut idt = IDT.lock();
+    for vector in 0..IDT_ENTRY_COUNT {
+        idt.set_handler(vector, unsafe { isr_stub_table[vector] }, code_selector);
+    }
+    idt.set_stack_index(8, DOUBLE_FAULT_IST_INDEX);
+    register_handler(8, exceptions::double_fault_handler);
+}
+
+/// Registers the handler of the specified vector.
+///
+/// Replaces the previously registered handler.
+pub fn register_handler(vector: u8, handler: InterruptHandler) {
+    without_interrupts(|| HANDLERS.lock()[vector as usize] = Some(handler));
+}
+
+/// Unregisters the handler of the specified vector.
+pub fn unregister_handler(vector: u8) {
+    without_interrupts(|| HANDLERS.lock()[vector as usize] = None);
+}
+
+/// Enables interrupts.
+pub fn enable() {
+    unsafe {
+        asm!("sti" :::: "volatile");
+    }
+}
+
+/// Enables interrupts and halts the CPU until the next interrupt arrives.
+///
+/// `sti` only takes effect after the following instruction, so an interrupt
+/// can't slip in between enabling interrupts and halting.
+pub fn enable_and_hlt() {
+    unsafe {
+        asm!("sti; hlt" :::: "volatile");
+    }
+}
+
+/// Disables interrupts.
+pub fn disable() {
+    unsafe {
+        asm!("cli" :::: "volatile");
+    }
+}
+
+/// Tests if interrupts are enabled.
+pub fn are_enabled() -> bool {
+    let rflags: u64;
+    unsafe {
+        asm!("pushfq; popq $0" : "=r" (rflags) ::: "volatile");
+    }
+    rflags & (1 << 9) != 0
+}
+
+/// Runs a closure with interrupts disabled.
+///
+/// Restores the previous interrupt state afterwards.
+pub fn without_interrupts<F, R>(f: F) -> R
+    where F: FnOnce() -> R
+{
+    let enabled = are_enabled();
+    if enabled {
+        disable();
+    }
+    let result = f();
+    if enabled {
+        enable();
+    }
+    result
+}
+
+/// Dispatches an interrupt to its registered handler.
+///
+/// Called by the interrupt stubs.
+#[no_mangle]
+pub extern "C" fn interrupt_dispatch(frame: &mut InterruptFrame) {
+    let handler = HANDLERS.lock()[frame.vector as usize];
+    match handler {
+        Some(handler) => handler(frame),
+        None if frame.vector < 32 => exceptions::unhandled_exception(frame),
+        None => warn!("Unhandled interrupt {}", frame.vector),
+    }
+}
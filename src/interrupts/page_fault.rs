@@ -0,0 +1,76 @@
+use memory::MEMORY_CONTROLLER;
+use memory;
+use super::InterruptFrame;
+use super::exceptions::{read_cr2, report_exception, print_report};
+
+// The page fault error code bit flags
+bitflags! {
+    pub flags PageFaultErrorCode: u64 {
+        const PROTECTION_VIOLATION = 1 << 0,
+        const CAUSED_BY_WRITE =      1 << 1,
+        const USER_MODE =            1 << 2,
+        const MALFORMED_TABLE =      1 << 3,
+        const INSTRUCTION_FETCH =    1 << 4,
+    }
+}
+
+/// Handles a page fault.
+///
+/// Faults on unmapped pages of lazily backed regions are resolved
+/// by mapping a zeroed frame. Every other fault is reported and panics.
+pub fn page_fault_handler(frame: &mut InterruptFrame) {
+    let addr = read_cr2() as usize;
+    let error_code = PageFaultErrorCode::from_bits_truncate(frame.error_code);
+
+    // Try resolving faults on pages that aren't present
+    if !error_code.intersects(PROTECTION_VIOLATION | MALFORMED_TABLE) {
+        if let Some(mut controller) = MEMORY_CONTROLLER.try_lock() {
+            if let Some(ref mut controller) = *controller {
+                if controller.resolve_page_fault(addr) {
+                    return;
+                }
+            }
+        }
+    }
+
+    // Report the fault
+    report_exception(frame);
+    print_report(format_args!("\tCause: {} during {} in {} mode{}\n",
+                              if error_code.contains(PROTECTION_VIOLATION) {
+                                  "protection violation"
+                              } else {
+                                  "page not present"
+                              },
+                              if error_code.contains(INSTRUCTION_FETCH) {
+                                  "instruction fetch"
+                              } else if error_code.contains(CAUSED_BY_WRITE) {
+                                  "write"
+                              } else {
+                                  "read"
+                              },
+                              if error_code.contains(USER_MODE) {
+                                  "user"
+                              } else {
+                                  "kernel"
+                              },
+                              if error_code.contains(MALFORMED_TABLE) {
+                                  ", reserved bit set in page table"
+                              } else {
+                                  ""
+                              }));
+    if let Some(controller) = MEMORY_CONTROLLER.try_lock() {
+        if let Some(ref controller) = *controller {
+            match controller.active_table.translate(addr) {
+                Some(phys_addr) => {
+                    print_report(format_args!("\tMapped to: 0x{:x}\n", phys_addr));
+                }
+                None => print_report(format_args!("\tMapped to: nothing\n")),
+            }
+        }
+    }
+    if memory::is_stack_guard_page(addr) {
+        print_report(format_args!("\tThe address is in a stack guard page, \
+                                   the stack probably overflowed\n"));
+    }
+    panic!("Unrecoverable page fault at 0x{:x}", addr);
+}
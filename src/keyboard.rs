@@ -0,0 +1,277 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+use cpuio::inb;
+use interrupts::{self, InterruptFrame};
+use pic;
+use ring_buffer::RingBuffer;
+
+/// The PS/2 data port.
+const DATA_PORT: u16 = 0x60;
+
+/// The PS/2 keyboard IRQ.
+const KEYBOARD_IRQ: u8 = 1;
+
+/// The prefix of extended scancodes.
+const EXTENDED_PREFIX: u8 = 0xe0;
+
+/// The flag set in the scancodes of released keys.
+const RELEASED: u8 = 0x80;
+
+/// The maximum number of hotkeys.
+const MAX_HOTKEYS: usize = 16;
+
+/// The characters of the scancode set 1 keys.
+static KEYMAP: &'static [u8; 0x3a] = b"\0\x1b1234567890-=\x08\tqwertyuiop[]\n\0asdfghjkl;'`\0\\zxcvbnm,./\0*\0 ";
+
+/// The characters of the scancode set 1 keys while shift is held.
+static KEYMAP_SHIFTED: &'static [u8; 0x3a] = b"\0\x1b!@#$%^&*()_+\x08\tQWERTYUIOP{}\n\0ASDFGHJKL:\"~\0|ZXCVBNM<>?\0*\0 ";
+
+/// The characters typed but not read yet.
+static INPUT: RingBuffer = RingBuffer::new();
+
+/// The pressed modifier keys, as bits.
+static MODIFIERS: AtomicUsize = AtomicUsize::new(0);
+
+/// The registered hotkeys.
+static HOTKEYS: Mutex<[Option<Hotkey>; MAX_HOTKEYS]> = Mutex::new([None; MAX_HOTKEYS]);
+
+/// The decoder state of the IRQ handler.
+static DECODER: Mutex<Decoder> = Mutex::new(Decoder { extended: false });
+
+bitflags! {
+    pub flags Modifiers: u8 {
+        const SHIFT = 1 << 0,
+        const CTRL =  1 << 1,
+        const ALT =   1 << 2,
+    }
+}
+
+/// The `Key` type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    /// A key that produces an ASCII character, holds the unshifted character.
+    Char(u8),
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Insert,
+    Delete,
+    /// A function key, holds its number starting at 1.
+    Function(u8),
+}
+
+/// The `KeyEvent` type.
+///
+/// Represents a pressed key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// The key.
+    pub key: Key,
+
+    /// The modifier keys held while the key was pressed.
+    pub modifiers: Modifiers,
+}
+
+/// The `HotkeyHandler` type.
+///
+/// Runs in the IRQ handler.
+pub type HotkeyHandler = fn(KeyEvent);
+
+/// The `Hotkey` type.
+#[derive(Copy, Clone)]
+struct Hotkey {
+    /// The key.
+    key: Key,
+
+    /// The modifier keys that must be held.
+    modifiers: Modifiers,
+
+    /// The handler.
+    handler: HotkeyHandler,
+}
+
+/// The `Decoder` type.
+///
+/// Turns scancodes into key events.
+struct Decoder {
+    /// Whether the previous scancode was the extended prefix.
+    extended: bool,
+}
+
+/// The `Decoder` implementation.
+impl Decoder {
+    /// Decodes a scancode of set 1.
+    ///
+    /// Returns a key event for pressed keys and tracks the modifier keys.
+    fn decode(&mut self, scancode: u8) -> Option<KeyEvent> {
+        if scancode == EXTENDED_PREFIX {
+            self.extended = true;
+            return None;
+        }
+        let extended = self.extended;
+        self.extended = false;
+        let released = scancode & RELEASED != 0;
+        let code = scancode & !RELEASED;
+
+        // Track the modifier keys
+        let modifier = match (extended, code) {
+            (false, 0x2a) | (false, 0x36) => Some(SHIFT),
+            (_, 0x1d) => Some(CTRL),
+            (_, 0x38) => Some(ALT),
+            _ => None,
+        };
+        if let Some(modifier) = modifier {
+            let bits = modifier.bits() as usize;
+            if released {
+                MODIFIERS.fetch_and(!bits, Ordering::SeqCst);
+            } else {
+                MODIFIERS.fetch_or(bits, Ordering::SeqCst);
+            }
+            return None;
+        }
+        if released {
+            return None;
+        }
+
+        let key = if extended {
+            match code {
+                0x48 => Key::Up,
+                0x50 => Key::Down,
+                0x4b => Key::Left,
+                0x4d => Key::Right,
+                0x49 => Key::PageUp,
+                0x51 => Key::PageDown,
+                0x47 => Key::Home,
+                0x4f => Key::End,
+                0x52 => Key::Insert,
+                0x53 => Key::Delete,
+                0x1c => Key::Char(b'\n'),
+                0x35 => Key::Char(b'/'),
+                _ => return None,
+            }
+        } else {
+            match code {
+                0x3b...0x44 => Key::Function(code - 0x3b + 1),
+                0x57 => Key::Function(11),
+                0x58 => Key::Function(12),
+                _ => {
+                    match KEYMAP.get(code as usize) {
+                        Some(&c) if c != 0 => Key::Char(c),
+                        _ => return None,
+                    }
+                }
+            }
+        };
+        Some(KeyEvent {
+            key: key,
+            modifiers: modifiers(),
+        })
+    }
+}
+
+/// The `KeyEvent` implementation.
+impl KeyEvent {
+    /// Gets the ASCII character typed by the event, if any.
+    pub fn ascii(&self) -> Option<u8> {
+        match self.key {
+            Key::Char(c) => {
+                let index = KEYMAP.iter().position(|&k| k == c);
+                let c = match index {
+                    Some(index) if self.modifiers.contains(SHIFT) => KEYMAP_SHIFTED[index],
+                    _ => c,
+                };
+                let lowercase = c | 0x20;
+                if self.modifiers.contains(CTRL) && lowercase >= b'a' && lowercase <= b'z' {
+                    // Control characters, like Ctrl+C
+                    Some(c & 0x1f)
+                } else {
+                    Some(c)
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Gets the modifier keys that are currently held.
+pub fn modifiers() -> Modifiers {
+    Modifiers::from_bits_truncate(MODIFIERS.load(Ordering::SeqCst) as u8)
+}
+
+/// Starts handling keyboard interrupts.
+pub fn init() {
+    // Discard a scancode that may be waiting
+    unsafe {
+        inb(DATA_PORT);
+    }
+    pic::register_irq_handler(KEYBOARD_IRQ, keyboard_irq_handler);
+}
+
+/// Registers a handler that's called instead of buffering the input when a
+/// key is pressed while exactly the specified modifier keys are held.
+///
+/// Returns false if there's no room for another hotkey.
+pub fn register_hotkey(key: Key, modifiers: Modifiers, handler: HotkeyHandler) -> bool {
+    interrupts::without_interrupts(|| {
+        let mut hotkeys = HOTKEYS.lock();
+        match hotkeys.iter_mut().find(|hotkey| hotkey.is_none()) {
+            Some(hotkey) => {
+                *hotkey = Some(Hotkey {
+                    key: key,
+                    modifiers: modifiers,
+                    handler: handler,
+                });
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Reads a typed character if one is available.
+pub fn try_read_byte() -> Option<u8> {
+    INPUT.pop()
+}
+
+/// Reads a typed character.
+///
+/// Sleeps until the next interrupt while no input is available.
+pub fn read_byte() -> u8 {
+    loop {
+        // Check the input with interrupts disabled,
+        // so no key arrives between the check and the halt
+        interrupts::disable();
+        if let Some(byte) = INPUT.pop() {
+            interrupts::enable();
+            return byte;
+        }
+        interrupts::enable_and_hlt();
+    }
+}
+
+/// Handles a keyboard interrupt.
+fn keyboard_irq_handler(_frame: &mut InterruptFrame) {
+    let scancode = unsafe { inb(DATA_PORT) };
+    let event = match DECODER.lock().decode(scancode) {
+        Some(event) => event,
+        None => return,
+    };
+
+    let hotkeys = *HOTKEYS.lock();
+    let hotkey = hotkeys.iter()
+        .filter_map(|hotkey| hotkey.as_ref())
+        .find(|hotkey| hotkey.key == event.key && hotkey.modifiers == event.modifiers);
+    match hotkey {
+        Some(hotkey) => (hotkey.handler)(event),
+        None => {
+            if let Some(c) = event.ascii() {
+                INPUT.push(c);
+            }
+        }
+    }
+}
@@ -2,9 +2,14 @@
 #![feature(const_fn)]
 #![feature(unique)]
 #![feature(asm)]
+#![feature(alloc, collections)]
 #![no_std]
 
 extern crate rlibc;
+extern crate alloc;
+#[macro_use]
+extern crate collections;
+extern crate heap_allocator;
 extern crate spin;
 extern crate cpuio;
 extern crate multiboot2;
@@ -17,22 +22,52 @@ use core::fmt::Write;
 #[macro_use]
 mod vga;
 use vga::{Console, Color, HalfColor};
+#[macro_use]
+mod log;
+use log::LogLevel;
 mod serial;
-use serial::COM1;
+use serial::{COM1, SerialConfig, SerialError};
 mod memory;
 use memory::FrameAllocator;
+mod interrupts;
+mod pic;
+mod cpu;
+mod acpi;
+mod apic;
+mod ring_buffer;
+mod pit;
+mod dmesg;
+mod keyboard;
 
 #[lang = "eh_personality"]
 extern "C" fn eh_personality() {}
 
 #[lang = "panic_fmt"]
 extern "C" fn panic_fmt(fmt: core::fmt::Arguments, file: &str, line: u32) -> ! {
-    Console.lock().set_cursor(0, 0);
-    Console.lock().set_color(Color::new(HalfColor::LightRed, HalfColor::Black));
-    println!("***\tKERNEL PANIC\n\tin {} at line {}:\n\t{}",
-             file,
-             line,
-             fmt);
+    // Show the report, even if another virtual console is active
+    vga::switch_console(0);
+
+    // Skip the screen or COM1 if the panicking code holds their lock
+    if let Some(mut console) = Console.try_lock() {
+        console.set_cursor(0, 0);
+        console.set_color(Color::new(HalfColor::LightRed, HalfColor::Black));
+        let _ = write!(console,
+                       "***\tKERNEL PANIC\n\tin {} at line {}:\n\t{}\n",
+                       file,
+                       line,
+                       fmt);
+    }
+
+    // Dump the kernel messages, including the ones that scrolled off the screen
+    if let Some(mut com1) = COM1.try_lock() {
+        let _ = com1.write_str("\n*** Kernel messages:\n");
+        dmesg::dump(&com1);
+        let _ = write!(com1,
+                       "\n***\tKERNEL PANIC\n\tin {} at line {}:\n\t{}\n",
+                       file,
+                       line,
+                       fmt);
+    }
     loop {}
 }
 
@@ -43,11 +78,48 @@ pub extern "C" fn kmain_setup(multiboot2_addr: usize) {
     // Clear the VGA buffer
     Console.lock().clear_screen();
 
+    // Log to the screen, COM1 and the kernel message buffer
+    log::add_sink(&Console, LogLevel::Info);
+    log::add_sink(&dmesg::DMESG, LogLevel::Trace);
+
+    // Initialize COM1
+    let com1 = COM1.lock().init(SerialConfig::default());
+    match com1 {
+        Ok(model) => info!("COM1 is a {:?}", model),
+        Err(SerialError::NotPresent) => warn!("COM1 is not present"),
+        Err(error) => warn!("COM1 could not be initialized: {:?}", error),
+    }
+    log::add_sink(&COM1, LogLevel::Trace);
+
+    // Install the exception handlers
+    interrupts::init();
+
     // Print multiboot2 debug information
     debug_print_multiboot2_info(multiboot2_addr);
 
-    // Initialize COM1
-    COM1.lock().init();
+    // Initialize the memory management
+    memory::init(multiboot2_addr);
+
+    // Set the double fault stack up
+    interrupts::init_gdt();
+
+    // Initialize the PICs, switch to the APICs if present and enable hardware interrupts
+    pic::init();
+    if !apic::init() {
+        warn!("No APIC found, using the legacy PIC");
+    }
+    interrupts::enable();
+
+    // Start the timer used for log timestamps
+    pit::init();
+
+    // Handle keyboard input, keep 500 lines of history on the
+    // first virtual console and switch consoles with Alt+F1..F6
+    keyboard::init();
+    vga::init(500);
+
+    // Buffer the input of COM1 in the background
+    COM1.lock().enable_rx_interrupt();
 
     // Test the serial writer
     write!(COM1.lock(), "Hello, world!");
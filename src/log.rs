@@ -0,0 +1,435 @@
+use core::cmp::min;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+use interrupts;
+use pit;
+use serial::SerialWriter;
+use vga::Writer;
+
+/// Logs a message at the specified level.
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => (
+        $crate::log::log($level, module_path!(), format_args!($($arg)*))
+    );
+}
+
+/// Logs an error.
+macro_rules! error {
+    ($($arg:tt)*) => (log!($crate::log::LogLevel::Error, $($arg)*));
+}
+
+/// Logs a warning.
+macro_rules! warn {
+    ($($arg:tt)*) => (log!($crate::log::LogLevel::Warn, $($arg)*));
+}
+
+/// Logs an informational message.
+macro_rules! info {
+    ($($arg:tt)*) => (log!($crate::log::LogLevel::Info, $($arg)*));
+}
+
+/// Logs a debug message.
+macro_rules! debug {
+    ($($arg:tt)*) => (log!($crate::log::LogLevel::Debug, $($arg)*));
+}
+
+/// Logs a trace message.
+macro_rules! trace {
+    ($($arg:tt)*) => (log!($crate::log::LogLevel::Trace, $($arg)*));
+}
+
+/// The maximum number of sinks.
+const MAX_SINKS: usize = 8;
+
+/// The maximum number of module filters.
+const MAX_FILTERS: usize = 16;
+
+/// The size of the memory log in bytes.
+pub const LOG_BUFFER_SIZE: usize = 16 * 1024;
+
+/// The maximum level of modules without a filter, as a `usize`.
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(LogLevel::Info as usize);
+
+/// The registered sinks.
+static SINKS: Mutex<[Option<SinkEntry>; MAX_SINKS]> = Mutex::new([None; MAX_SINKS]);
+
+/// The module filters.
+static FILTERS: Mutex<[Option<ModuleFilter>; MAX_FILTERS]> = Mutex::new([None; MAX_FILTERS]);
+
+/// The `LogLevel` type.
+///
+/// Lower levels are more important.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// The `LogLevel` implementation.
+impl LogLevel {
+    /// Gets the name, padded to the same width for every level.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN ",
+            LogLevel::Info => "INFO ",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Trace => "TRACE",
+        }
+    }
+
+    /// Gets the ANSI escape sequence selecting the color of the level.
+    fn ansi_color(&self) -> &'static str {
+        match *self {
+            LogLevel::Error => "\x1b[1;31m",
+            LogLevel::Warn => "\x1b[1;33m",
+            LogLevel::Info => "\x1b[1;32m",
+            LogLevel::Debug => "\x1b[1;36m",
+            LogLevel::Trace => "\x1b[1;30m",
+        }
+    }
+
+    /// Converts a `usize` back into a `LogLevel`.
+    fn from_usize(level: usize) -> Option<LogLevel> {
+        match level {
+            1 => Some(LogLevel::Error),
+            2 => Some(LogLevel::Warn),
+            3 => Some(LogLevel::Info),
+            4 => Some(LogLevel::Debug),
+            5 => Some(LogLevel::Trace),
+            _ => None,
+        }
+    }
+}
+
+/// The `Record` type.
+///
+/// Represents a single log message.
+pub struct Record<'a> {
+    /// The level.
+    pub level: LogLevel,
+
+    /// The path of the module that logged the message.
+    pub module: &'static str,
+
+    /// The milliseconds since boot.
+    pub timestamp: usize,
+
+    /// The message.
+    pub args: fmt::Arguments<'a>,
+}
+
+/// The `Record` implementation.
+impl<'a> Record<'a> {
+    /// Writes the timestamp, the level and the module path.
+    pub fn write_prefix<W: Write>(&self, writer: &mut W) -> fmt::Result {
+        write!(writer,
+               "[{:5}.{:03}] {} {}: ",
+               self.timestamp / 1000,
+               self.timestamp % 1000,
+               self.level.name(),
+               self.module)
+    }
+
+    /// Writes the whole record as a line.
+    pub fn write_line<W: Write>(&self, writer: &mut W) -> fmt::Result {
+        try!(self.write_prefix(writer));
+        try!(writer.write_fmt(self.args));
+        writer.write_char('\n')
+    }
+
+    /// Writes the whole record as a line, with the level colored
+    /// by ANSI escape sequences.
+    ///
+    /// Looks the same on the VGA console and on a serial terminal.
+    pub fn write_colored_line<W: Write>(&self, writer: &mut W) -> fmt::Result {
+        write!(writer,
+               "[{:5}.{:03}] {}{}\x1b[0m {}: {}\n",
+               self.timestamp / 1000,
+               self.timestamp % 1000,
+               self.level.ansi_color(),
+               self.level.name(),
+               self.module,
+               self.args)
+    }
+}
+
+/// The `Sink` trait.
+///
+/// Implemented by everything log records can be written to.
+/// Records are written with interrupts enabled, so a sink must not
+/// block on a lock that an interrupted context might hold.
+pub trait Sink: Sync {
+    /// Writes a record.
+    fn write_record(&self, record: &Record);
+}
+
+/// The `Sink` implementation for the VGA console.
+///
+/// Colors the level by importance. Drops the record
+/// if the console is busy, it's still kept by dmesg.
+impl Sink for Mutex<Writer> {
+    fn write_record(&self, record: &Record) {
+        interrupts::without_interrupts(|| {
+            if let Some(mut writer) = self.try_lock() {
+                let _ = record.write_colored_line(&mut *writer);
+            }
+        });
+    }
+}
+
+/// The `Sink` implementation for serial ports.
+///
+/// Colors the level like the VGA console does. Keeps interrupts
+/// enabled while writing, since the port is slow, and drops the
+/// record if the port is busy.
+impl Sink for Mutex<SerialWriter> {
+    fn write_record(&self, record: &Record) {
+        if let Some(mut writer) = self.try_lock() {
+            let _ = record.write_colored_line(&mut *writer);
+        }
+    }
+}
+
+/// The `Sink` implementation for memory logs.
+impl Sink for Mutex<LogBuffer> {
+    fn write_record(&self, record: &Record) {
+        interrupts::without_interrupts(|| {
+            let _ = record.write_line(&mut *self.lock());
+        });
+    }
+}
+
+/// The `SinkEntry` type.
+#[derive(Copy, Clone)]
+struct SinkEntry {
+    /// The sink.
+    sink: &'static Sink,
+
+    /// The maximum level written to the sink.
+    level: LogLevel,
+}
+
+/// The `ModuleFilter` type.
+#[derive(Copy, Clone)]
+struct ModuleFilter {
+    /// The module path, applies to submodules as well.
+    module: &'static str,
+
+    /// The maximum level, `None` if the module is silenced.
+    level: Option<LogLevel>,
+}
+
+/// The `ModuleFilter` implementation.
+impl ModuleFilter {
+    /// Tests if the filter applies to the specified module.
+    fn matches(&self, module: &str) -> bool {
+        module.starts_with(self.module) &&
+        (module.len() == self.module.len() || module[self.module.len()..].starts_with("::"))
+    }
+}
+
+/// The `LogBuffer` type.
+///
+/// Holds the most recent log output, overwriting the oldest bytes when full.
+pub struct LogBuffer {
+    /// The bytes.
+    data: [u8; LOG_BUFFER_SIZE],
+
+    /// The index of the oldest byte.
+    start: usize,
+
+    /// The number of bytes.
+    len: usize,
+
+    /// The number of bytes ever written.
+    written: usize,
+}
+
+/// The `::core::fmt::Write` implementation for `LogBuffer`.
+impl Write for LogBuffer {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.write_bytes(string.as_bytes());
+        Ok(())
+    }
+}
+
+/// The `LogBuffer` implementation.
+impl LogBuffer {
+    /// Constructs a new empty `LogBuffer`.
+    pub const fn new() -> LogBuffer {
+        LogBuffer {
+            data: [0; LOG_BUFFER_SIZE],
+            start: 0,
+            len: 0,
+            written: 0,
+        }
+    }
+
+    /// Appends bytes, overwriting the oldest ones if the buffer is full.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            let end = (self.start + self.len) % LOG_BUFFER_SIZE;
+            self.data[end] = byte;
+            if self.len == LOG_BUFFER_SIZE {
+                self.start = (self.start + 1) % LOG_BUFFER_SIZE;
+            } else {
+                self.len += 1;
+            }
+        }
+        self.written = self.written.wrapping_add(bytes.len());
+    }
+
+    /// Gets the contents, oldest bytes first.
+    ///
+    /// The contents wrap around the end of the buffer, so they're split in two slices.
+    pub fn contents(&self) -> (&[u8], &[u8]) {
+        if self.start + self.len <= LOG_BUFFER_SIZE {
+            (&self.data[self.start..self.start + self.len], &[])
+        } else {
+            let end = (self.start + self.len) % LOG_BUFFER_SIZE;
+            (&self.data[self.start..], &self.data[..end])
+        }
+    }
+
+    /// Gets the number of bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Gets the number of bytes ever written.
+    ///
+    /// Serves as the position behind the newest byte for `read_at`.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Copies bytes starting at an absolute position into a buffer.
+    ///
+    /// Positions count every byte ever written, so readers can resume where
+    /// they left off. Skips ahead if the position has been overwritten.
+    /// Copies nothing if the position hasn't been written yet.
+    /// Returns the number of copied bytes and the position to continue at.
+    pub fn read_at(&self, position: usize, buffer: &mut [u8]) -> (usize, usize) {
+        if position > self.written {
+            return (0, position);
+        }
+        let oldest = self.written - self.len;
+        let skipped = if position < oldest { 0 } else { position - oldest };
+        let count = min(self.len - skipped, buffer.len());
+        for (i, byte) in buffer[..count].iter_mut().enumerate() {
+            *byte = self.data[(self.start + skipped + i) % LOG_BUFFER_SIZE];
+        }
+        (count, oldest + skipped + count)
+    }
+
+    /// Removes every byte.
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+}
+
+/// Registers a sink that receives records up to the specified level.
+///
+/// Returns false if there's no room for another sink.
+pub fn add_sink(sink: &'static Sink, level: LogLevel) -> bool {
+    interrupts::without_interrupts(|| {
+        let mut sinks = SINKS.lock();
+        match sinks.iter_mut().find(|entry| entry.is_none()) {
+            Some(entry) => {
+                *entry = Some(SinkEntry {
+                    sink: sink,
+                    level: level,
+                });
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Unregisters a sink.
+pub fn remove_sink(sink: &'static Sink) {
+    let address = sink as *const Sink as *const u8;
+    interrupts::without_interrupts(|| {
+        for entry in SINKS.lock().iter_mut() {
+            let matches = entry.map_or(false, |e| e.sink as *const Sink as *const u8 == address);
+            if matches {
+                *entry = None;
+            }
+        }
+    });
+}
+
+/// Sets the maximum level of modules without a filter.
+pub fn set_level(level: LogLevel) {
+    MAX_LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+/// Sets the maximum level of a module and its submodules.
+///
+/// `None` silences the module. Module paths start with the crate name,
+/// like `rite::memory`. The longest matching filter wins.
+pub fn set_module_level(module: &'static str, level: Option<LogLevel>) -> bool {
+    interrupts::without_interrupts(|| {
+        let mut filters = FILTERS.lock();
+        if let Some(filter) = filters.iter_mut()
+            .filter_map(|filter| filter.as_mut())
+            .find(|filter| filter.module == module) {
+            filter.level = level;
+            return true;
+        }
+        match filters.iter_mut().find(|filter| filter.is_none()) {
+            Some(filter) => {
+                *filter = Some(ModuleFilter {
+                    module: module,
+                    level: level,
+                });
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Gets the maximum level of the specified module.
+fn max_level(module: &str) -> Option<LogLevel> {
+    let filters = FILTERS.lock();
+    let filter = filters.iter()
+        .filter_map(|filter| filter.as_ref())
+        .filter(|filter| filter.matches(module))
+        .max_by_key(|filter| filter.module.len());
+    match filter {
+        Some(filter) => filter.level,
+        None => LogLevel::from_usize(MAX_LEVEL.load(Ordering::Relaxed)),
+    }
+}
+
+/// Writes a message to every sink that accepts its level.
+///
+/// Used by the logging macros. Only the filters and the sink list are
+/// accessed with interrupts disabled, the sinks run on a copy of the list.
+pub fn log(level: LogLevel, module: &'static str, args: fmt::Arguments) {
+    let (module_level, sinks) = interrupts::without_interrupts(|| {
+        (max_level(module), *SINKS.lock())
+    });
+    if module_level.map_or(true, |max| level > max) {
+        return;
+    }
+    let record = Record {
+        level: level,
+        module: module,
+        timestamp: pit::uptime_ms(),
+        args: args,
+    };
+    for entry in sinks.iter().filter_map(|entry| entry.as_ref()) {
+        if level <= entry.level {
+            entry.sink.write_record(&record);
+        }
+    }
+}
@@ -1,6 +1,16 @@
-use memory::{Frame, FrameAllocator};
+use memory::{PAGE_SIZE, MAX_PHYSICAL_MEMORY, Frame, FrameAllocator};
+use memory::bitmap::Bitmap;
 use multiboot2::{MemoryArea, MemoryAreaIter};
 
+/// The number of words in the freed frame bitmap.
+const FREED_FRAMES_WORDS: usize = MAX_PHYSICAL_MEMORY / PAGE_SIZE / 64;
+
+/// The storage of the freed frame bitmap.
+///
+/// Lives in `.bss` so it doesn't depend on any mapping
+/// besides the kernel image itself.
+static mut FREED_FRAMES: [u64; FREED_FRAMES_WORDS] = [0; FREED_FRAMES_WORDS];
+
 /// The `AreaFrameAllocator` type.
 pub struct AreaFrameAllocator {
     /// The next free frame.
@@ -23,12 +33,80 @@ pub struct AreaFrameAllocator {
 
     /// The end address of the multiboot2 data.
     mb2_end: Frame,
+
+    /// The frames that were deallocated and can be handed out again.
+    freed: Bitmap,
+
+    /// The number of frames in the freed frame bitmap.
+    freed_count: usize,
+
+    /// The lowest index that may be set in the freed frame bitmap.
+    freed_hint: usize,
 }
 
 /// The `FrameAllocator` implementation for `AreaFrameAllocator`.
 impl FrameAllocator for AreaFrameAllocator {
     /// Allocates a frame.
+    ///
+    /// Reuses deallocated frames before taking fresh ones.
     fn alloc_frame(&mut self) -> Option<Frame> {
+        // Test if there are any deallocated frames
+        if self.freed_count > 0 {
+            let index = self.freed.find_set(self.freed_hint).unwrap();
+            self.freed.clear(index);
+            self.freed_count -= 1;
+            self.freed_hint = index + 1;
+            return Some(Frame { index: index });
+        }
+        self.alloc_fresh_frame()
+    }
+
+    /// Deallocates a frame.
+    fn dealloc_frame(&mut self, frame: Frame) {
+        assert!(frame < self.next_frame && self.is_usable(&frame),
+                "Deallocated frame 0x{:x} was never allocated",
+                frame.index);
+        assert!(!self.freed.get(frame.index),
+                "Frame 0x{:x} was deallocated twice",
+                frame.index);
+        self.freed.set(frame.index);
+        self.freed_count += 1;
+        if frame.index < self.freed_hint {
+            self.freed_hint = frame.index;
+        }
+    }
+}
+
+/// The `AreaFrameAllocator` implementation.
+impl AreaFrameAllocator {
+    /// Constructs a new `AreaFrameAllocator`.
+    ///
+    /// Only one `AreaFrameAllocator` may exist at a time,
+    /// because all of them share the freed frame bitmap.
+    pub fn new(kernel_start: usize,
+               kernel_end: usize,
+               mb2_start: usize,
+               mb2_end: usize,
+               areas: MemoryAreaIter)
+               -> AreaFrameAllocator {
+        let mut allocator = AreaFrameAllocator {
+            next_frame: Frame::get_frame_for_address(0),
+            area: None,
+            areas: areas,
+            kernel_start: Frame::get_frame_for_address(kernel_start),
+            kernel_end: Frame::get_frame_for_address(kernel_end),
+            mb2_start: Frame::get_frame_for_address(mb2_start),
+            mb2_end: Frame::get_frame_for_address(mb2_end),
+            freed: Bitmap::new(unsafe { &mut FREED_FRAMES }),
+            freed_count: 0,
+            freed_hint: 0,
+        };
+        allocator.find_free_area();
+        allocator
+    }
+
+    /// Allocates a frame that was never handed out before.
+    fn alloc_fresh_frame(&mut self) -> Option<Frame> {
         // Test if the current area is invalid
         if self.area.is_none() {
             return None;
@@ -44,8 +122,12 @@ impl FrameAllocator for AreaFrameAllocator {
             Frame::get_frame_for_address(addr as usize)
         };
 
+        // Test if the frame can't be tracked by the freed frame bitmap
+        if frame.index >= self.freed.len() {
+            return None;
+        }
         // Test if the frame exceeds the bounds of the current area
-        if frame > last_frame {
+        else if frame > last_frame {
             self.find_free_area();
         }
         // Test if the frame is within the bounds of the kernel
@@ -61,35 +143,23 @@ impl FrameAllocator for AreaFrameAllocator {
         }
 
         // Try allocating a new frame
-        self.alloc_frame()
-    }
-
-    /// Deallocates a frame.
-    fn dealloc_frame(&mut self, frame: Frame) {
-        unimplemented!();
+        self.alloc_fresh_frame()
     }
-}
 
-/// The `AreaFrameAllocator` implementation.
-impl AreaFrameAllocator {
-    /// Constructs a new `AreaFrameAllocator`.
-    pub fn new(kernel_start: usize,
-               kernel_end: usize,
-               mb2_start: usize,
-               mb2_end: usize,
-               areas: MemoryAreaIter)
-               -> AreaFrameAllocator {
-        let mut allocator = AreaFrameAllocator {
-            next_frame: Frame::get_frame_for_address(0),
-            area: None,
-            areas: areas,
-            kernel_start: Frame::get_frame_for_address(kernel_start),
-            kernel_end: Frame::get_frame_for_address(kernel_end),
-            mb2_start: Frame::get_frame_for_address(mb2_start),
-            mb2_end: Frame::get_frame_for_address(mb2_end),
-        };
-        allocator.find_free_area();
-        allocator
+    /// Tests if a frame lies in a memory area and outside the kernel
+    /// and the multiboot2 data.
+    fn is_usable(&self, frame: &Frame) -> bool {
+        if *frame >= self.kernel_start && *frame <= self.kernel_end {
+            return false;
+        }
+        if *frame >= self.mb2_start && *frame <= self.mb2_end {
+            return false;
+        }
+        self.areas.clone().any(|area| {
+            let first = Frame::get_frame_for_address(area.base_addr as usize);
+            let last = Frame::get_frame_for_address((area.base_addr + area.length - 1) as usize);
+            *frame >= first && *frame <= last
+        })
     }
 
     /// Finds a free memory area.
@@ -0,0 +1,62 @@
+/// The number of bits in a bitmap word.
+const WORD_BITS: usize = 64;
+
+/// The `Bitmap` type.
+///
+/// Represents a fixed-size set of bits backed by static storage.
+pub struct Bitmap {
+    /// The words.
+    words: &'static mut [u64],
+}
+
+/// The `Bitmap` implementation.
+impl Bitmap {
+    /// Constructs a new `Bitmap` with all bits cleared.
+    pub fn new(words: &'static mut [u64]) -> Bitmap {
+        for word in words.iter_mut() {
+            *word = 0;
+        }
+        Bitmap { words: words }
+    }
+
+    /// Gets the number of bits.
+    pub fn len(&self) -> usize {
+        self.words.len() * WORD_BITS
+    }
+
+    /// Tests if the specified bit is set.
+    pub fn get(&self, index: usize) -> bool {
+        self.words[index / WORD_BITS] & (1 << (index % WORD_BITS)) != 0
+    }
+
+    /// Sets the specified bit.
+    pub fn set(&mut self, index: usize) {
+        self.words[index / WORD_BITS] |= 1 << (index % WORD_BITS);
+    }
+
+    /// Clears the specified bit.
+    pub fn clear(&mut self, index: usize) {
+        self.words[index / WORD_BITS] &= !(1 << (index % WORD_BITS));
+    }
+
+    /// Finds the first set bit at or after the specified index.
+    pub fn find_set(&self, start: usize) -> Option<usize> {
+        let mut word_index = start / WORD_BITS;
+        if word_index >= self.words.len() {
+            return None;
+        }
+
+        // Mask off the bits below the start index in the first word
+        let mut word = self.words[word_index] & (!0u64 << (start % WORD_BITS));
+        loop {
+            if word != 0 {
+                return Some(word_index * WORD_BITS + word.trailing_zeros() as usize);
+            }
+            word_index += 1;
+            if word_index >= self.words.len() {
+                return None;
+            }
+            word = self.words[word_index];
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use memory::{PAGE_SIZE, MAX_PHYSICAL_MEMORY, Frame, FrameAllocator};
+use memory::bitmap::Bitmap;
+use multiboot2::MemoryAreaIter;
+
+/// The largest block order.
+///
+/// A block of order `n` consists of `2^n` contiguous frames,
+/// so the largest block spans 4 MiB.
+pub const MAX_ORDER: usize = 10;
+
+/// The number of block orders.
+const ORDER_COUNT: usize = MAX_ORDER + 1;
+
+/// The number of frames that can be tracked.
+const FRAME_COUNT: usize = MAX_PHYSICAL_MEMORY / PAGE_SIZE;
+
+/// The number of words needed for the free block bitmaps of all orders.
+///
+/// The bitmap of order `n` has `FRAME_COUNT >> n` bits, so all of
+/// them together need less than twice the bits of the order 0 bitmap.
+const FREE_BLOCKS_WORDS: usize = 2 * FRAME_COUNT / 64;
+
+/// The storage of the free block bitmaps.
+static mut FREE_BLOCKS: [u64; FREE_BLOCKS_WORDS] = [0; FREE_BLOCKS_WORDS];
+
+/// The `BuddyFrameAllocator` type.
+///
+/// Manages physical memory as power-of-two sized blocks
+/// of naturally aligned, contiguous frames.
+pub struct BuddyFrameAllocator {
+    /// The free block bitmaps of all orders.
+    ///
+    /// Bit `offsets[n] + i` is set if the block of order `n`
+    /// starting at frame `i << n` is free.
+    free_blocks: Bitmap,
+
+    /// The bit offset of the free block bitmap of each order.
+    offsets: [usize; ORDER_COUNT],
+
+    /// The number of free blocks of each order.
+    free_counts: [usize; ORDER_COUNT],
+
+    /// The lowest block index that may be free for each order.
+    hints: [usize; ORDER_COUNT],
+}
+
+/// The `FrameAllocator` implementation for `BuddyFrameAllocator`.
+impl FrameAllocator for BuddyFrameAllocator {
+    /// Allocates a frame.
+    fn alloc_frame(&mut self) -> Option<Frame> {
+        self.alloc_frames(0)
+    }
+
+    /// Deallocates a frame.
+    fn dealloc_frame(&mut self, frame: Frame) {
+        self.dealloc_frames(frame, 0)
+    }
+}
+
+/// The `BuddyFrameAllocator` implementation.
+impl BuddyFrameAllocator {
+    /// Constructs a new `BuddyFrameAllocator`.
+    ///
+    /// Every frame of the memory areas is made available, except
+    /// for the frames occupied by the kernel and the multiboot2 data.
+    /// Only one `BuddyFrameAllocator` may exist at a time,
+    /// because all of them share the free block bitmaps.
+    pub fn new(kernel_start: usize,
+               kernel_end: usize,
+               mb2_start: usize,
+               mb2_end: usize,
+               areas: MemoryAreaIter)
+               -> BuddyFrameAllocator {
+        let mut offsets = [0; ORDER_COUNT];
+        for order in 1..ORDER_COUNT {
+            offsets[order] = offsets[order - 1] + (FRAME_COUNT >> (order - 1));
+        }
+        let mut allocator = BuddyFrameAllocator {
+            free_blocks: Bitmap::new(unsafe { &mut FREE_BLOCKS }),
+            offsets: offsets,
+            free_counts: [0; ORDER_COUNT],
+            hints: [0; ORDER_COUNT],
+        };
+
+        // Get the excluded frame ranges, sorted by their start frame
+        let mut excluded = [(kernel_start / PAGE_SIZE, kernel_end / PAGE_SIZE),
+                            (mb2_start / PAGE_SIZE, mb2_end / PAGE_SIZE)];
+        if excluded[1].0 < excluded[0].0 {
+            excluded.swap(0, 1);
+        }
+
+        // Free all frames that are fully contained in the memory areas
+        for area in areas {
+            let start = (area.base_addr as usize + PAGE_SIZE - 1) / PAGE_SIZE;
+            let end = (area.base_addr + area.length) as usize / PAGE_SIZE;
+            let end = if end > FRAME_COUNT { FRAME_COUNT } else { end };
+            let mut current = start;
+            for &(excluded_start, excluded_end) in excluded.iter() {
+                if excluded_end < current || excluded_start >= end {
+                    continue;
+                }
+                if excluded_start > current {
+                    allocator.free_range(current, excluded_start);
+                }
+                current = excluded_end + 1;
+            }
+            if current < end {
+                allocator.free_range(current, end);
+            }
+        }
+        allocator
+    }
+
+    /// Allocates `2^order` contiguous frames.
+    ///
+    /// The first frame is aligned to `2^order` frames.
+    pub fn alloc_frames(&mut self, order: usize) -> Option<Frame> {
+        assert!(order <= MAX_ORDER, "Invalid block order {}", order);
+
+        // Find the smallest free block that is large enough
+        let mut current = match (order..ORDER_COUNT).find(|&o| self.free_counts[o] > 0) {
+            Some(current) => current,
+            None => return None,
+        };
+        let mut block = self.take_block(current);
+
+        // Split the block and free the upper halves
+        while current > order {
+            current -= 1;
+            block *= 2;
+            self.insert_block(current, block + 1);
+        }
+        Some(Frame { index: block << order })
+    }
+
+    /// Deallocates `2^order` contiguous frames.
+    ///
+    /// The frames must have been allocated by `alloc_frames`
+    /// using the same order.
+    pub fn dealloc_frames(&mut self, frame: Frame, order: usize) {
+        assert!(order <= MAX_ORDER, "Invalid block order {}", order);
+        assert!(frame.index % (1 << order) == 0,
+                "Frame 0x{:x} is not aligned to order {}",
+                frame.index,
+                order);
+        assert!(frame.index + (1 << order) <= FRAME_COUNT,
+                "Deallocated frame 0x{:x} was never allocated",
+                frame.index);
+        // The block may also be part of a larger free block it was merged into
+        assert!((order..ORDER_COUNT).all(|o| !self.is_free(o, frame.index >> o)),
+                "Frame 0x{:x} was deallocated twice",
+                frame.index);
+        let mut current = order;
+        let mut block = frame.index >> order;
+
+        // Merge the block with its buddy as long as the buddy is free
+        while current < MAX_ORDER && self.is_free(current, block ^ 1) {
+            self.remove_block(current, block ^ 1);
+            block /= 2;
+            current += 1;
+        }
+        self.insert_block(current, block);
+    }
+
+    /// Gets the number of free frames.
+    pub fn free_frames(&self) -> usize {
+        (0..ORDER_COUNT).map(|order| self.free_counts[order] << order).sum()
+    }
+
+    /// Frees the frames in the specified range.
+    ///
+    /// The range is split into the largest possible aligned blocks.
+    fn free_range(&mut self, start: usize, end: usize) {
+        let mut current = start;
+        while current < end {
+            let mut order = MAX_ORDER;
+            while current % (1 << order) != 0 || current + (1 << order) > end {
+                order -= 1;
+            }
+            self.dealloc_frames(Frame { index: current }, order);
+            current += 1 << order;
+        }
+    }
+
+    /// Gets the bitmap index of the specified block.
+    fn bit(&self, order: usize, block: usize) -> usize {
+        self.offsets[order] + block
+    }
+
+    /// Tests if the specified block is free.
+    fn is_free(&self, order: usize, block: usize) -> bool {
+        block < (FRAME_COUNT >> order) && self.free_blocks.get(self.bit(order, block))
+    }
+
+    /// Marks the specified block as free.
+    fn insert_block(&mut self, order: usize, block: usize) {
+        let bit = self.bit(order, block);
+        self.free_blocks.set(bit);
+        self.free_counts[order] += 1;
+        if block < self.hints[order] {
+            self.hints[order] = block;
+        }
+    }
+
+    /// Marks the specified free block as used.
+    fn remove_block(&mut self, order: usize, block: usize) {
+        let bit = self.bit(order, block);
+        self.free_blocks.clear(bit);
+        self.free_counts[order] -= 1;
+    }
+
+    /// Takes the lowest free block of the specified order.
+    fn take_block(&mut self, order: usize) -> usize {
+        let start = self.bit(order, self.hints[order]);
+        let block = self.free_blocks.find_set(start).unwrap() - self.offsets[order];
+        self.remove_block(order, block);
+        self.hints[order] = block + 1;
+        block
+    }
+}
@@ -1,10 +1,328 @@
 /// The size of a page.
 pub const PAGE_SIZE: usize = 4096;
 
+/// The virtual address the kernel is linked at.
+///
+/// The kernel is loaded at its virtual address minus this offset.
+/// Must match `KERNEL_OFFSET` in `linker.ld` and `boot.asm`.
+pub const KERNEL_OFFSET: usize = 0xffff_ffff_8000_0000;
+
+/// The virtual address all physical memory is mapped at.
+pub const PHYSMAP_OFFSET: usize = 0xffff_8000_0000_0000;
+
+/// The size of the direct physical memory map.
+pub const PHYSMAP_SIZE: usize = 64 * 1024 * 1024 * 1024 * 1024;
+
+/// The amount of physical memory the frame allocators can track.
+///
+/// Frames above this limit are never handed out.
+pub const MAX_PHYSICAL_MEMORY: usize = 4 * 1024 * 1024 * 1024;
+
+/// The start address of the kernel heap.
+pub const HEAP_START: usize = 0xffff_fe80_0000_0000;
+
+/// The size of the kernel heap.
+pub const HEAP_SIZE: usize = 1024 * 1024;
+
+/// The start address of the area slab pages are mapped to.
+pub const SLAB_AREA_START: usize = 0xffff_fe00_0000_0000;
+
+/// The size of the slab area.
+pub const SLAB_AREA_SIZE: usize = 512 * 1024 * 1024 * 1024;
+
+/// The start address of the area kernel stacks are mapped to.
+pub const STACK_AREA_START: usize = 0xffff_fd80_0000_0000;
+
+/// The size of the stack area.
+pub const STACK_AREA_SIZE: usize = 512 * 1024 * 1024 * 1024;
+
+use core::ptr;
+use collections::Vec;
+use spin::Mutex;
+use multiboot2;
+use heap_allocator;
+use cpu;
+
+mod bitmap;
 mod area_alloc;
 pub use self::area_alloc::AreaFrameAllocator;
-mod paging;
-use self::paging::PhysicalAddress;
+mod buddy_alloc;
+pub use self::buddy_alloc::{BuddyFrameAllocator, MAX_ORDER};
+mod slab;
+pub use self::slab::{SlabCache, SlabStats};
+mod stack_alloc;
+pub use self::stack_alloc::{Stack, StackAllocator};
+pub mod paging;
+use self::paging::{PhysicalAddress, VirtualAddress, ActivePageTable, Page, EntryFlags, WRITABLE,
+                   NO_EXECUTE};
+
+/// The memory controller.
+///
+/// Available once `init` was called.
+pub static MEMORY_CONTROLLER: Mutex<Option<MemoryController>> = Mutex::new(None);
+
+/// The `MemoryController` type.
+///
+/// Owns the active page table and the frame allocator.
+pub struct MemoryController {
+    /// The active page table.
+    pub active_table: ActivePageTable,
+
+    /// The frame allocator.
+    pub frame_allocator: BuddyFrameAllocator,
+
+    /// The stack allocator.
+    pub stack_allocator: StackAllocator,
+
+    /// The regions that are backed by frames on first access.
+    lazy_regions: Vec<LazyRegion>,
+}
+
+/// The `LazyRegion` type.
+///
+/// Represents a virtual memory region whose pages
+/// are only backed by frames once they're touched.
+struct LazyRegion {
+    /// The start address.
+    start: VirtualAddress,
+
+    /// The end address, exclusive.
+    end: VirtualAddress,
+
+    /// The flags the pages are mapped with.
+    flags: EntryFlags,
+}
+
+/// The `MemoryController` implementation.
+impl MemoryController {
+    /// Allocates a guarded kernel stack of the specified size.
+    pub fn alloc_stack(&mut self, size_in_pages: usize) -> Option<Stack> {
+        self.stack_allocator.alloc_stack(&mut self.active_table,
+                                         &mut self.frame_allocator,
+                                         size_in_pages)
+    }
+
+    /// Deallocates a kernel stack.
+    pub fn dealloc_stack(&mut self, stack: Stack) {
+        self.stack_allocator.dealloc_stack(stack, &mut self.active_table, &mut self.frame_allocator)
+    }
+
+    /// Maps a physical memory region into the direct physical memory map.
+    ///
+    /// Used for memory the bootloader didn't report as usable, like firmware
+    /// tables and memory mapped devices. Pages that are already mapped keep
+    /// their flags and gain the requested ones, so a device overlapping
+    /// the physical memory map still becomes uncacheable.
+    /// Returns the virtual address of the region.
+    pub fn map_physical_region(&mut self,
+                               start: PhysicalAddress,
+                               size: usize,
+                               flags: EntryFlags)
+                               -> VirtualAddress {
+        let start_frame = Frame::get_frame_for_address(start);
+        let end_frame = Frame::get_frame_for_address(start + size - 1);
+        for frame in Frame::range_inclusive(start_frame, end_frame) {
+            let page = Page::get_page_at_address(phys_to_virt(frame.get_start_address()));
+            if self.active_table.translate_page(page).is_none() {
+                self.active_table.map_to(page, frame, flags | NO_EXECUTE, &mut self.frame_allocator);
+            } else {
+                self.active_table.add_flags(page, flags);
+            }
+        }
+        phys_to_virt(start)
+    }
+
+    /// Registers a region that is backed by zeroed frames on first access.
+    ///
+    /// The region must be page aligned and must not be mapped yet.
+    pub fn add_lazy_region(&mut self, start: VirtualAddress, size: usize, flags: EntryFlags) {
+        assert!(start % PAGE_SIZE == 0 && size % PAGE_SIZE == 0,
+                "Lazy region at 0x{:x} is not page aligned",
+                start);
+        self.lazy_regions.push(LazyRegion {
+            start: start,
+            end: start + size,
+            flags: flags,
+        });
+    }
+
+    /// Tries to resolve a page fault on a page that isn't present.
+    ///
+    /// Returns whether the page at the address is mapped now.
+    pub fn resolve_page_fault(&mut self, addr: VirtualAddress) -> bool {
+        let page = Page::get_page_at_address(addr);
+
+        // Test if the fault was caused by a stale TLB entry
+        if self.active_table.translate_page(page).is_some() {
+            unsafe {
+                // Flush translation lookaside buffer
+                asm!("invlpg ($0)" :: "r" (page.address()) : "memory");
+            }
+            return true;
+        }
+
+        // Get the lazy region containing the address
+        let flags = match self.lazy_regions.iter().find(|r| addr >= r.start && addr < r.end) {
+            Some(region) => region.flags,
+            None => return false,
+        };
+
+        // Back the page with a zeroed frame
+        let frame = match self.frame_allocator.alloc_frame() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        unsafe {
+            ptr::write_bytes(phys_to_virt(frame.get_start_address()) as *mut u8, 0, PAGE_SIZE);
+        }
+        self.active_table.map_to(page, frame, flags, &mut self.frame_allocator);
+        true
+    }
+}
+
+extern "C" {
+    /// The page below the bootstrap stack.
+    ///
+    /// Defined in `boot.asm`.
+    static stack_guard_page: u8;
+}
+
+/// Initializes the memory management.
+///
+/// Sets the frame allocator up, remaps the kernel together with the
+/// direct physical memory map, maps the kernel heap and sets the stack allocator up.
+pub fn init(multiboot2_addr: usize) {
+    // Get the multiboot2 data
+    let mb2_info = unsafe { multiboot2::load(multiboot2_addr) };
+    let memory_map = mb2_info.memory_map_tag().expect("Memory map tag required");
+    let elf_sections = mb2_info.elf_sections_tag().expect("Elf sections tag required");
+
+    // Get the physical kernel and multiboot2 memory bounds
+    let kernel_start = elf_sections.sections()
+        .filter(|s| s.is_allocated())
+        .map(|s| kernel_to_physical(s.addr as usize))
+        .min()
+        .unwrap();
+    let kernel_end = elf_sections.sections()
+        .filter(|s| s.is_allocated())
+        .map(|s| kernel_to_physical((s.addr + s.size) as usize))
+        .max()
+        .unwrap();
+    let mb2_start = multiboot2_addr;
+    let mb2_end = mb2_start + (mb2_info.total_size as usize);
+
+    // Set the frame allocator up
+    let mut frame_allocator = BuddyFrameAllocator::new(kernel_start,
+                                                       kernel_end,
+                                                       mb2_start,
+                                                       mb2_end,
+                                                       memory_map.memory_areas());
+
+    // Remap the kernel with the permissions of its sections
+    enable_nxe_bit();
+    let mut active_table = paging::remap_the_kernel(&mut frame_allocator, mb2_info);
+
+    // Turn the page below the bootstrap stack into a guard page
+    let stack_guard_page = Page::get_page_at_address(unsafe { &stack_guard_page } as *const _ as
+                                                     usize);
+    active_table.unmap(stack_guard_page, &mut frame_allocator);
+
+    // Map the kernel heap
+    let heap_start_page = Page::get_page_at_address(HEAP_START);
+    let heap_end_page = Page::get_page_at_address(HEAP_START + HEAP_SIZE - 1);
+    for page in Page::range_inclusive(heap_start_page, heap_end_page) {
+        active_table.map(page, WRITABLE | NO_EXECUTE, &mut frame_allocator);
+    }
+    unsafe {
+        heap_allocator::init(HEAP_START, HEAP_SIZE);
+    }
+
+    // Set the stack allocator up
+    let stack_allocator = {
+        let stack_start_page = Page::get_page_at_address(STACK_AREA_START);
+        let stack_end_page = Page::get_page_at_address(STACK_AREA_START + STACK_AREA_SIZE - 1);
+        StackAllocator::new(Page::range_inclusive(stack_start_page, stack_end_page))
+    };
+
+    *MEMORY_CONTROLLER.lock() = Some(MemoryController {
+        active_table: active_table,
+        frame_allocator: frame_allocator,
+        stack_allocator: stack_allocator,
+        lazy_regions: Vec::new(),
+    });
+}
+
+/// Tests if an address is within a stack guard page.
+///
+/// Only the guard pages of the bootstrap stack and of stacks that are
+/// still allocated count, so an access to a freed stack isn't reported
+/// as an overflow.
+pub fn is_stack_guard_page(addr: VirtualAddress) -> bool {
+    let boot_guard_page = unsafe { &stack_guard_page } as *const _ as usize;
+    if addr >= boot_guard_page && addr < boot_guard_page + PAGE_SIZE {
+        return true;
+    }
+    if addr < STACK_AREA_START || addr >= STACK_AREA_START + STACK_AREA_SIZE {
+        return false;
+    }
+    match MEMORY_CONTROLLER.try_lock() {
+        Some(controller) => {
+            match *controller {
+                Some(ref controller) => controller.stack_allocator.is_guard_page(addr),
+                None => false,
+            }
+        }
+        None => false,
+    }
+}
+
+/// Gets the physical address of an address within the kernel image.
+///
+/// The bootstrap code is linked at its physical address,
+/// everything else at its physical address plus `KERNEL_OFFSET`.
+pub fn kernel_to_physical(addr: usize) -> PhysicalAddress {
+    if addr >= KERNEL_OFFSET {
+        addr - KERNEL_OFFSET
+    } else {
+        addr
+    }
+}
+
+/// Gets the address a physical address is mapped at in the direct physical memory map.
+///
+/// Only physical memory that was reported as usable by the bootloader is mapped.
+pub fn phys_to_virt(addr: PhysicalAddress) -> VirtualAddress {
+    assert!(addr < PHYSMAP_SIZE,
+            "Physical address 0x{:x} is outside of the physical memory map",
+            addr);
+    addr + PHYSMAP_OFFSET
+}
+
+/// Gets the physical address of an address in the direct
+/// physical memory map or in the kernel image.
+///
+/// Other addresses need to be translated using `Mapper::translate`.
+pub fn virt_to_phys(addr: VirtualAddress) -> PhysicalAddress {
+    if addr >= KERNEL_OFFSET {
+        addr - KERNEL_OFFSET
+    } else if addr >= PHYSMAP_OFFSET && addr < PHYSMAP_OFFSET + PHYSMAP_SIZE {
+        addr - PHYSMAP_OFFSET
+    } else {
+        panic!("Virtual address 0x{:x} is not linearly mapped", addr);
+    }
+}
+
+/// Enables the no-execute bit in the EFER register.
+///
+/// Without it, the `NO_EXECUTE` entry flag is a reserved bit.
+fn enable_nxe_bit() {
+    let nxe_bit = 1 << 11;
+    let efer = 0xC0000080;
+    unsafe {
+        let value = cpu::read_msr(efer);
+        cpu::write_msr(efer, value | nxe_bit);
+    }
+}
 
 /// The `Frame` type.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -40,6 +358,39 @@ impl Frame {
     fn get_start_address(&self) -> PhysicalAddress {
         self.index * PAGE_SIZE
     }
+
+    /// Gets an iterator over the frames from `start` to `end`, inclusive.
+    fn range_inclusive(start: Frame, end: Frame) -> FrameIter {
+        FrameIter {
+            start: start,
+            end: end,
+        }
+    }
+}
+
+/// The `FrameIter` type.
+struct FrameIter {
+    /// The next frame.
+    start: Frame,
+
+    /// The last frame.
+    end: Frame,
+}
+
+/// The `Iterator` implementation for `FrameIter`.
+impl Iterator for FrameIter {
+    type Item = Frame;
+
+    /// Gets the next frame.
+    fn next(&mut self) -> Option<Frame> {
+        if self.start <= self.end {
+            let frame = self.start.clone();
+            self.start.index += 1;
+            Some(frame)
+        } else {
+            None
+        }
+    }
 }
 
 /// The `FrameAllocator` trait.
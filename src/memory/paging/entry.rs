@@ -1,4 +1,5 @@
 use memory::Frame;
+use multiboot2::ElfSection;
 
 // The page table entry bit flags
 bitflags! {
@@ -13,6 +14,25 @@ bitflags! {
         const HUGE_PAGE =       1 << 7,
         const GLOBAL =          1 << 8,
         const NO_EXECUTE =      1 << 63,
+    }
+}
+
+/// The `EntryFlags` implementation.
+impl EntryFlags {
+    /// Gets the entry flags matching the flags of an ELF section.
+    pub fn from_elf_section_flags(section: &ElfSection) -> EntryFlags {
+        use multiboot2::{ELF_SECTION_ALLOCATED, ELF_SECTION_WRITABLE, ELF_SECTION_EXECUTABLE};
+        let mut flags = EntryFlags::empty();
+        if section.flags().contains(ELF_SECTION_ALLOCATED) {
+            flags = flags | PRESENT;
+        }
+        if section.flags().contains(ELF_SECTION_WRITABLE) {
+            flags = flags | WRITABLE;
+        }
+        if !section.flags().contains(ELF_SECTION_EXECUTABLE) {
+            flags = flags | NO_EXECUTE;
+        }
+        flags
     }
 }
 
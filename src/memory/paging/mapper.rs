@@ -1,5 +1,5 @@
 use core::ptr::Unique;
-use super::{VirtualAddress, PhysicalAddress, Page, ENTRY_COUNT};
+use super::{VirtualAddress, PhysicalAddress, Page, HugePageSize, ENTRY_COUNT};
 use super::entry::*;
 use super::table::{self, Table, Level4, Level1};
 use memory::{PAGE_SIZE, Frame, FrameAllocator};
@@ -35,13 +35,41 @@ impl Mapper {
     }
 
     /// Translates a page into a frame.
+    ///
+    /// Also translates pages within 1 GiB and 2 MiB huge pages.
     pub fn translate_page(&self, page: Page) -> Option<Frame> {
-        use super::entry::HUGE_PAGE;
         let p3 = self.p4().next_table(page.p4_index());
-        let huge_page = || unimplemented!();
+        let huge_page = || {
+            p3.and_then(|p3| {
+                // Test if the P3 entry maps a 1 GiB page
+                let p3_entry = &p3[page.p3_index()];
+                if let Some(start_frame) = p3_entry.frame() {
+                    if p3_entry.flags().contains(HUGE_PAGE) {
+                        assert!(start_frame.index % (ENTRY_COUNT * ENTRY_COUNT) == 0);
+                        return Some(Frame {
+                            index: start_frame.index + page.p2_index() * ENTRY_COUNT +
+                                   page.p1_index(),
+                        });
+                    }
+                }
+
+                // Test if the P2 entry maps a 2 MiB page
+                if let Some(p2) = p3.next_table(page.p3_index()) {
+                    let p2_entry = &p2[page.p2_index()];
+                    if let Some(start_frame) = p2_entry.frame() {
+                        if p2_entry.flags().contains(HUGE_PAGE) {
+                            assert!(start_frame.index % ENTRY_COUNT == 0);
+                            return Some(Frame { index: start_frame.index + page.p1_index() });
+                        }
+                    }
+                }
+                None
+            })
+        };
         p3.and_then(|p3| p3.next_table(page.p3_index()))
             .and_then(|p2| p2.next_table(page.p2_index()))
             .and_then(|p1| p1[page.p1_index()].frame())
+            .or_else(huge_page)
     }
 
     /// Maps a page to a frame using the specified allocator.
@@ -55,6 +83,98 @@ impl Mapper {
         p1[page.p1_index()].set_flags(frame, flags | PRESENT);
     }
 
+    /// Maps a huge page to a frame using the specified allocator.
+    ///
+    /// The page and the frame must be aligned to the huge page size.
+    /// 1 GiB pages are only available on CPUs that support them.
+    pub fn map_to_huge<A>(&mut self,
+                          page: Page,
+                          frame: Frame,
+                          size: HugePageSize,
+                          flags: EntryFlags,
+                          allocator: &mut A)
+        where A: FrameAllocator
+    {
+        assert!(page.index % size.frame_count() == 0,
+                "Page 0x{:x} is not aligned to the huge page size",
+                page.address());
+        assert!(frame.index % size.frame_count() == 0,
+                "Frame 0x{:x} is not aligned to the huge page size",
+                frame.get_start_address());
+        let mut p3 = self.p4_mut().create_next_table(page.p4_index(), allocator);
+        match size {
+            HugePageSize::Size1GiB => {
+                assert!(p3[page.p3_index()].is_unused());
+                p3[page.p3_index()].set_flags(frame, flags | PRESENT | HUGE_PAGE);
+            }
+            HugePageSize::Size2MiB => {
+                let mut p2 = p3.create_next_table(page.p3_index(), allocator);
+                assert!(p2[page.p2_index()].is_unused());
+                p2[page.p2_index()].set_flags(frame, flags | PRESENT | HUGE_PAGE);
+            }
+        }
+    }
+
+    /// Adds flags to the entry of a mapped page.
+    ///
+    /// Huge pages must already have the flags, since
+    /// changing them would affect every page they cover.
+    pub fn add_flags(&mut self, page: Page, flags: EntryFlags) {
+        let p3 = self.p4_mut()
+            .next_table_mut(page.p4_index())
+            .expect("Page is not mapped");
+        if p3[page.p3_index()].flags().contains(HUGE_PAGE) {
+            assert!(p3[page.p3_index()].flags().contains(flags),
+                    "The flags of huge page 0x{:x} can't be changed",
+                    page.address());
+            return;
+        }
+        let p2 = p3.next_table_mut(page.p3_index()).expect("Page is not mapped");
+        if p2[page.p2_index()].flags().contains(HUGE_PAGE) {
+            assert!(p2[page.p2_index()].flags().contains(flags),
+                    "The flags of huge page 0x{:x} can't be changed",
+                    page.address());
+            return;
+        }
+        let p1 = p2.next_table_mut(page.p2_index()).expect("Page is not mapped");
+        let entry = &mut p1[page.p1_index()];
+        let frame = entry.frame().expect("Page is not mapped");
+        if !entry.flags().contains(flags) {
+            let new_flags = entry.flags() | flags;
+            entry.set_flags(frame, new_flags);
+            unsafe {
+                // Flush translation lookaside buffer
+                asm!("invlpg ($0)" :: "r" (page.address()) : "memory");
+            }
+        }
+    }
+
+    /// Merges flags into the entry of a mapped page.
+    ///
+    /// The page allows everything either the old or the new flags allow,
+    /// so it's writable if either is and executable if either is.
+    pub fn merge_flags(&mut self, page: Page, flags: EntryFlags) {
+        let p1 = self.p4_mut()
+            .next_table_mut(page.p4_index())
+            .and_then(|p3| p3.next_table_mut(page.p3_index()))
+            .and_then(|p2| p2.next_table_mut(page.p2_index()))
+            .expect("Huge pages can't be merged");
+        let entry = &mut p1[page.p1_index()];
+        let frame = entry.frame().expect("Page is not mapped");
+        let old_flags = entry.flags();
+        let mut new_flags = old_flags | flags;
+        if !old_flags.contains(NO_EXECUTE) || !flags.contains(NO_EXECUTE) {
+            new_flags.remove(NO_EXECUTE);
+        }
+        if new_flags != old_flags {
+            entry.set_flags(frame, new_flags);
+            unsafe {
+                // Flush translation lookaside buffer
+                asm!("invlpg ($0)" :: "r" (page.address()) : "memory");
+            }
+        }
+    }
+
     /// Maps the next free page using the specified allocator.
     pub fn map<A>(&mut self, page: Page, flags: EntryFlags, allocator: &mut A)
         where A: FrameAllocator
@@ -80,7 +200,7 @@ impl Mapper {
             .next_table_mut(page.p4_index())
             .and_then(|p3| p3.next_table_mut(page.p3_index()))
             .and_then(|p2| p2.next_table_mut(page.p2_index()))
-            .unwrap();
+            .expect("Huge pages must be unmapped using unmap_huge");
         let frame = p1[page.p1_index()].frame().unwrap();
         p1[page.p1_index()].mark_unused();
         unsafe {
@@ -89,4 +209,31 @@ impl Mapper {
         }
         allocator.dealloc_frame(frame);
     }
+
+    /// Unmaps a huge page.
+    ///
+    /// Returns the first frame of the huge page, since the frames
+    /// can't be given back through the single-frame allocator interface.
+    pub fn unmap_huge(&mut self, page: Page, size: HugePageSize) -> Frame {
+        let p3 = self.p4_mut()
+            .next_table_mut(page.p4_index())
+            .expect("Huge page is not mapped");
+        let entry = match size {
+            HugePageSize::Size1GiB => &mut p3[page.p3_index()],
+            HugePageSize::Size2MiB => {
+                &mut p3.next_table_mut(page.p3_index())
+                    .expect("Huge page is not mapped")[page.p2_index()]
+            }
+        };
+        assert!(entry.flags().contains(HUGE_PAGE),
+                "Page 0x{:x} is not a huge page",
+                page.address());
+        let frame = entry.frame().expect("Huge page is not mapped");
+        entry.mark_unused();
+        unsafe {
+            // Flush translation lookaside buffer
+            asm!("invlpg ($0)" :: "r" (page.address()) : "memory");
+        }
+        frame
+    }
 }
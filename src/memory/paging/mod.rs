@@ -9,7 +9,8 @@ mod temp_page;
 pub use self::entry::*;
 pub use self::table::{Level1, Table};
 
-use super::{Frame, PAGE_SIZE};
+use multiboot2::BootInformation;
+use super::{Frame, PAGE_SIZE, KERNEL_OFFSET, PHYSMAP_SIZE};
 use super::FrameAllocator;
 use self::table::{Level4, LEVEL4_TABLE};
 use self::temp_page::TemporaryPage;
@@ -18,6 +19,11 @@ use self::mapper::Mapper;
 /// The number of entries.
 const ENTRY_COUNT: usize = 512;
 
+/// The P4 index of the recursive mapping.
+///
+/// Entry 511 is taken by the higher half kernel.
+const RECURSIVE_INDEX: usize = 510;
+
 /// The `PhysicalAddress` type.
 pub type PhysicalAddress = usize;
 
@@ -52,7 +58,7 @@ impl DerefMut for ActivePageTable {
 /// The `ActivePageTable` implementation.
 impl ActivePageTable {
     /// Constructs a new `ActivePageTable`.
-    unsafe fn new() -> ActivePageTable {
+    pub unsafe fn new() -> ActivePageTable {
         ActivePageTable { mapper: Mapper::new() }
     }
 
@@ -75,14 +81,32 @@ impl ActivePageTable {
                 cr3
             });
             let p4_table = temporary_page.map_table_frame(backup.clone(), self);
-            self.p4_mut()[511].set_flags(table.p4_frame.clone(), PRESENT | WRITABLE);
+            self.p4_mut()[RECURSIVE_INDEX].set_flags(table.p4_frame.clone(), PRESENT | WRITABLE);
             flush_tlb();
             f(self);
-            p4_table[511].set_flags(backup, PRESENT | WRITABLE);
+            p4_table[RECURSIVE_INDEX].set_flags(backup, PRESENT | WRITABLE);
             flush_tlb();
         }
         temporary_page.unmap(self);
     }
+
+    /// Switches to the specified page table.
+    ///
+    /// Loads the new level 4 table into CR3 and
+    /// returns the previously active page table.
+    pub fn switch(&mut self, new_table: InactivePageTable) -> InactivePageTable {
+        let old_table = InactivePageTable {
+            p4_frame: Frame::get_frame_for_address(unsafe {
+                let cr3: usize;
+                asm!("mov %cr3, $0" : "=r" (cr3));
+                cr3
+            }),
+        };
+        unsafe {
+            asm!("mov $0, %cr3" :: "r" (new_table.p4_frame.get_start_address()) : "memory");
+        }
+        old_table
+    }
 }
 
 /// The `InactivePageTable` type.
@@ -101,13 +125,144 @@ impl InactivePageTable {
         {
             let table = temporary_page.map_table_frame(frame.clone(), active_table);
             table.zero_fill();
-            table[511].set_flags(frame.clone(), PRESENT | WRITABLE);
+            table[RECURSIVE_INDEX].set_flags(frame.clone(), PRESENT | WRITABLE);
         }
         temporary_page.unmap(active_table);
         InactivePageTable { p4_frame: frame }
     }
 }
 
+/// The `HugePageSize` type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HugePageSize {
+    /// A 2 MiB page, mapped by a P2 entry.
+    Size2MiB,
+
+    /// A 1 GiB page, mapped by a P3 entry.
+    Size1GiB,
+}
+
+/// The `HugePageSize` implementation.
+impl HugePageSize {
+    /// Gets the number of frames covered by a huge page.
+    pub fn frame_count(&self) -> usize {
+        match *self {
+            HugePageSize::Size2MiB => ENTRY_COUNT,
+            HugePageSize::Size1GiB => ENTRY_COUNT * ENTRY_COUNT,
+        }
+    }
+
+    /// Gets the size of a huge page in bytes.
+    pub fn size(&self) -> usize {
+        self.frame_count() * PAGE_SIZE
+    }
+}
+
+/// Remaps the kernel into a new page table and activates it.
+///
+/// Every allocated ELF section is mapped with the permissions its
+/// flags ask for, so code isn't writable and data isn't executable.
+/// A page shared by several sections gets the permissions of all of them.
+/// The VGA buffer, the multiboot2 data and the direct
+/// physical memory map are mapped as well.
+/// Drops the identity mapping of the first GiB set up by `paging.asm`.
+pub fn remap_the_kernel<A>(allocator: &mut A, mb2_info: &BootInformation) -> ActivePageTable
+    where A: FrameAllocator
+{
+    let mut temporary_page = TemporaryPage::new(Page::get_page_at_address(0xcafebabe000),
+                                                allocator);
+    let mut active_table = unsafe { ActivePageTable::new() };
+    let mut new_table = {
+        let frame = allocator.alloc_frame().expect("Out of memory");
+        InactivePageTable::new(frame, &mut active_table, &mut temporary_page)
+    };
+
+    active_table.with(&mut new_table, &mut temporary_page, |mapper| {
+        let elf_sections = mb2_info.elf_sections_tag().expect("Elf sections tag required");
+
+        // Map the kernel sections
+        for section in elf_sections.sections() {
+            if !section.is_allocated() || section.size == 0 {
+                continue;
+            }
+            let flags = EntryFlags::from_elf_section_flags(section);
+            let start_page = Page::get_page_at_address(section.addr as usize);
+            let end_page = Page::get_page_at_address((section.addr + section.size - 1) as usize);
+            for page in Page::range_inclusive(start_page, end_page) {
+                if mapper.translate_page(page).is_some() {
+                    mapper.merge_flags(page, flags);
+                    continue;
+                }
+                let frame = Frame::get_frame_for_address(super::kernel_to_physical(page.address()));
+                mapper.map_to(page, frame, flags, allocator);
+            }
+        }
+
+        // Map the VGA buffer
+        let vga_buffer_page = Page::get_page_at_address(KERNEL_OFFSET + 0xb8000);
+        let vga_buffer_frame = Frame::get_frame_for_address(0xb8000);
+        mapper.map_to(vga_buffer_page, vga_buffer_frame, WRITABLE | NO_EXECUTE, allocator);
+
+        // Map the multiboot2 data
+        let mb2_start = mb2_info as *const _ as usize;
+        let mb2_end = mb2_start + (mb2_info.total_size as usize) - 1;
+        for frame in Frame::range_inclusive(Frame::get_frame_for_address(mb2_start),
+                                            Frame::get_frame_for_address(mb2_end)) {
+            let page = Page::get_page_at_address(frame.get_start_address());
+            if mapper.translate_page(page).is_some() {
+                continue;
+            }
+            mapper.identitiy_map(frame, PRESENT | NO_EXECUTE, allocator);
+        }
+
+        // Map all physical memory at PHYSMAP_OFFSET
+        let memory_map = mb2_info.memory_map_tag().expect("Memory map tag required");
+        for area in memory_map.memory_areas() {
+            map_physical_range(mapper,
+                               area.base_addr as usize,
+                               (area.base_addr + area.length) as usize,
+                               allocator);
+        }
+    });
+
+    // Activate the new page table
+    active_table.switch(new_table);
+    active_table
+}
+
+/// Maps a range of physical memory into the direct physical memory map.
+///
+/// Only whole frames within the range are mapped.
+/// Uses 2 MiB pages wherever the range allows it.
+fn map_physical_range<A>(mapper: &mut Mapper,
+                         start: PhysicalAddress,
+                         end: PhysicalAddress,
+                         allocator: &mut A)
+    where A: FrameAllocator
+{
+    let huge_page_size = HugePageSize::Size2MiB.size();
+    let mut addr = (start + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    let end = end & !(PAGE_SIZE - 1);
+    assert!(end <= PHYSMAP_SIZE,
+            "Physical memory at 0x{:x} doesn't fit into the physical memory map",
+            end);
+    while addr < end {
+        let page = Page::get_page_at_address(super::phys_to_virt(addr));
+        let frame = Frame::get_frame_for_address(addr);
+        if addr % huge_page_size == 0 && addr + huge_page_size <= end {
+            mapper.map_to_huge(page,
+                               frame,
+                               HugePageSize::Size2MiB,
+                               WRITABLE | NO_EXECUTE,
+                               allocator);
+            addr += huge_page_size;
+        } else {
+            mapper.map_to(page, frame, WRITABLE | NO_EXECUTE, allocator);
+            addr += PAGE_SIZE;
+        }
+    }
+}
+
 /// The `Page` type.
 #[derive(Debug, Copy, Clone)]
 pub struct Page {
@@ -127,6 +282,14 @@ impl Page {
         self.index * PAGE_SIZE
     }
 
+    /// Gets an iterator over the pages from `start` to `end`, inclusive.
+    pub fn range_inclusive(start: Page, end: Page) -> PageIter {
+        PageIter {
+            start: start,
+            end: end,
+        }
+    }
+
     /// Gets the P4 index.
     fn p4_index(&self) -> usize {
         (self.index >> 27) & 0o777
@@ -147,3 +310,29 @@ impl Page {
         self.index & 0o777
     }
 }
+
+/// The `PageIter` type.
+#[derive(Clone)]
+pub struct PageIter {
+    /// The next page.
+    start: Page,
+
+    /// The last page.
+    end: Page,
+}
+
+/// The `Iterator` implementation for `PageIter`.
+impl Iterator for PageIter {
+    type Item = Page;
+
+    /// Gets the next page.
+    fn next(&mut self) -> Option<Page> {
+        if self.start.index <= self.end.index {
+            let page = self.start;
+            self.start.index += 1;
+            Some(page)
+        } else {
+            None
+        }
+    }
+}
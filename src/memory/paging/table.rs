@@ -5,7 +5,9 @@ use memory::paging::ENTRY_COUNT;
 use memory::FrameAllocator;
 
 /// The level 4 table.
-pub const LEVEL4_TABLE: *mut Table<Level4> = 0xfffffffffffff000 as *mut _;
+///
+/// Reachable through the recursive mapping in P4 entry 510.
+pub const LEVEL4_TABLE: *mut Table<Level4> = 0xffffff7fbfdfe000 as *mut _;
 
 /// The `TableLevel` trait.
 pub trait TableLevel {}
@@ -133,7 +135,14 @@ impl<L> Table<L>
         let entry_flags = self[index].flags();
         if entry_flags.contains(PRESENT) && !entry_flags.contains(HUGE_PAGE) {
             let table_addr = self as *const _ as usize;
-            Some((table_addr << 9) | (index << 12))
+            let addr = ((table_addr << 9) | (index << 12)) & 0x0000_ffff_ffff_f000;
+
+            // Sign-extend the address to make it canonical
+            if addr & (1 << 47) != 0 {
+                Some(addr | 0xffff_0000_0000_0000)
+            } else {
+                Some(addr)
+            }
         } else {
             None
         }
@@ -0,0 +1,346 @@
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use memory::{PAGE_SIZE, SLAB_AREA_START, SLAB_AREA_SIZE, FrameAllocator};
+use memory::paging::{ActivePageTable, Page, WRITABLE, NO_EXECUTE};
+
+/// The byte freed objects are filled with in builds with the `slab_poison` feature.
+const POISON_FREE: u8 = 0x6b;
+
+/// The next unused page of the slab area.
+static NEXT_SLAB_PAGE: AtomicUsize = AtomicUsize::new(SLAB_AREA_START);
+
+/// The `FreeObject` type.
+///
+/// Occupies the first word of every free object.
+struct FreeObject {
+    /// The next free object of the slab.
+    next: *mut FreeObject,
+}
+
+/// The `SlabHeader` type.
+///
+/// Sits at the start of every slab page, followed by the objects.
+struct SlabHeader {
+    /// The previous slab in the same list.
+    prev: *mut SlabHeader,
+
+    /// The next slab in the same list.
+    next: *mut SlabHeader,
+
+    /// The first free object.
+    free: *mut FreeObject,
+
+    /// The number of allocated objects.
+    in_use: usize,
+}
+
+/// The `SlabStats` type.
+#[derive(Debug, Copy, Clone)]
+pub struct SlabStats {
+    /// The number of allocations.
+    pub allocations: usize,
+
+    /// The number of deallocations.
+    pub deallocations: usize,
+
+    /// The number of slab pages.
+    pub slabs: usize,
+
+    /// The number of allocated objects.
+    pub active_objects: usize,
+
+    /// The number of objects in all slab pages.
+    pub total_objects: usize,
+}
+
+/// The `SlabCache` type.
+///
+/// Hands out fixed-size objects from page-sized slabs.
+pub struct SlabCache {
+    /// The name.
+    name: &'static str,
+
+    /// The size of an object slot.
+    object_size: usize,
+
+    /// The offset of the first object in a slab.
+    first_object: usize,
+
+    /// The number of objects per slab.
+    objects_per_slab: usize,
+
+    /// The constructor, called on every allocated object.
+    constructor: Option<fn(*mut u8)>,
+
+    /// The slabs with both free and allocated objects.
+    partial: *mut SlabHeader,
+
+    /// The slabs without free objects.
+    full: *mut SlabHeader,
+
+    /// The slabs without allocated objects.
+    empty: *mut SlabHeader,
+
+    /// The statistics.
+    stats: SlabStats,
+}
+
+/// The `Send` implementation for `SlabCache`.
+///
+/// The slabs are owned by the cache.
+unsafe impl Send for SlabCache {}
+
+/// The `SlabCache` implementation.
+impl SlabCache {
+    /// Constructs a new `SlabCache`.
+    ///
+    /// The alignment must be a power of two and the
+    /// objects must fit at least eight times into a slab.
+    pub fn new(name: &'static str,
+               size: usize,
+               align: usize,
+               constructor: Option<fn(*mut u8)>)
+               -> SlabCache {
+        assert!(align.is_power_of_two(), "Invalid slab alignment {}", align);
+        let align = if align < mem::align_of::<FreeObject>() {
+            mem::align_of::<FreeObject>()
+        } else {
+            align
+        };
+        let size = if size < mem::size_of::<FreeObject>() {
+            mem::size_of::<FreeObject>()
+        } else {
+            size
+        };
+        let object_size = align_up(size, align);
+        let first_object = align_up(mem::size_of::<SlabHeader>(), align);
+        let objects_per_slab = (PAGE_SIZE - first_object) / object_size;
+        assert!(objects_per_slab >= 8, "Slab objects of {} bytes are too large", size);
+        SlabCache {
+            name: name,
+            object_size: object_size,
+            first_object: first_object,
+            objects_per_slab: objects_per_slab,
+            constructor: constructor,
+            partial: ptr::null_mut(),
+            full: ptr::null_mut(),
+            empty: ptr::null_mut(),
+            stats: SlabStats {
+                allocations: 0,
+                deallocations: 0,
+                slabs: 0,
+                active_objects: 0,
+                total_objects: 0,
+            },
+        }
+    }
+
+    /// Gets the name.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Gets the statistics.
+    pub fn stats(&self) -> SlabStats {
+        self.stats
+    }
+
+    /// Allocates an object.
+    ///
+    /// Maps a new slab using the specified allocator if all slabs are full.
+    pub fn alloc<A>(&mut self,
+                    active_table: &mut ActivePageTable,
+                    allocator: &mut A)
+                    -> Option<*mut u8>
+        where A: FrameAllocator
+    {
+        unsafe {
+            // Get a slab with free objects
+            if self.partial.is_null() {
+                if self.empty.is_null() {
+                    if !self.grow(active_table, allocator) {
+                        return None;
+                    }
+                }
+                let slab = self.empty;
+                remove(&mut self.empty, slab);
+                push(&mut self.partial, slab);
+            }
+            let slab = self.partial;
+
+            // Take the first free object
+            let object = (*slab).free;
+            (*slab).free = (*object).next;
+            (*slab).in_use += 1;
+            if (*slab).in_use == self.objects_per_slab {
+                remove(&mut self.partial, slab);
+                push(&mut self.full, slab);
+            }
+            self.check_poison(object as *mut u8);
+
+            self.stats.allocations += 1;
+            self.stats.active_objects += 1;
+            if let Some(constructor) = self.constructor {
+                constructor(object as *mut u8);
+            }
+            Some(object as *mut u8)
+        }
+    }
+
+    /// Deallocates an object.
+    ///
+    /// Panics if the object is already free.
+    pub fn dealloc(&mut self, object: *mut u8) {
+        let slab = (object as usize & !(PAGE_SIZE - 1)) as *mut SlabHeader;
+        let offset = object as usize - slab as usize;
+        assert!(offset >= self.first_object && (offset - self.first_object) % self.object_size == 0,
+                "Object 0x{:x} doesn't belong to slab cache {}",
+                object as usize,
+                self.name);
+        unsafe {
+            assert!((*slab).in_use > 0 && !self.is_free(slab, object),
+                    "Object 0x{:x} of slab cache {} was deallocated twice",
+                    object as usize,
+                    self.name);
+            self.poison(object);
+            let object = object as *mut FreeObject;
+            (*object).next = (*slab).free;
+            (*slab).free = object;
+
+            // Move the slab into the matching list
+            if (*slab).in_use == self.objects_per_slab {
+                remove(&mut self.full, slab);
+                push(&mut self.partial, slab);
+            }
+            (*slab).in_use -= 1;
+            if (*slab).in_use == 0 {
+                remove(&mut self.partial, slab);
+                push(&mut self.empty, slab);
+            }
+        }
+        self.stats.deallocations += 1;
+        self.stats.active_objects -= 1;
+    }
+
+    /// Unmaps all empty slabs and returns their frames to the allocator.
+    pub fn shrink<A>(&mut self, active_table: &mut ActivePageTable, allocator: &mut A)
+        where A: FrameAllocator
+    {
+        while !self.empty.is_null() {
+            let slab = self.empty;
+            unsafe {
+                remove(&mut self.empty, slab);
+            }
+            active_table.unmap(Page::get_page_at_address(slab as usize), allocator);
+            self.stats.slabs -= 1;
+            self.stats.total_objects -= self.objects_per_slab;
+        }
+    }
+
+    /// Maps a new slab and adds it to the empty slabs.
+    unsafe fn grow<A>(&mut self, active_table: &mut ActivePageTable, allocator: &mut A) -> bool
+        where A: FrameAllocator
+    {
+        let frame = match allocator.alloc_frame() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let addr = NEXT_SLAB_PAGE.fetch_add(PAGE_SIZE, Ordering::SeqCst);
+        assert!(addr < SLAB_AREA_START + SLAB_AREA_SIZE, "The slab area is exhausted");
+        let page = Page::get_page_at_address(addr);
+        active_table.map_to(page, frame, WRITABLE | NO_EXECUTE, allocator);
+
+        // Chain all objects into the free list
+        let slab = addr as *mut SlabHeader;
+        let mut free = ptr::null_mut();
+        for i in (0..self.objects_per_slab).rev() {
+            let object = (addr + self.first_object + i * self.object_size) as *mut FreeObject;
+            self.poison(object as *mut u8);
+            (*object).next = free;
+            free = object;
+        }
+        ptr::write(slab,
+                   SlabHeader {
+                       prev: ptr::null_mut(),
+                       next: ptr::null_mut(),
+                       free: free,
+                       in_use: 0,
+                   });
+        push(&mut self.empty, slab);
+
+        self.stats.slabs += 1;
+        self.stats.total_objects += self.objects_per_slab;
+        true
+    }
+
+    /// Tests if an object is on the free list of its slab.
+    unsafe fn is_free(&self, slab: *mut SlabHeader, object: *mut u8) -> bool {
+        let mut free = (*slab).free;
+        while !free.is_null() {
+            if free as *mut u8 == object {
+                return true;
+            }
+            free = (*free).next;
+        }
+        false
+    }
+
+    /// Fills a free object with the poison byte.
+    ///
+    /// The first word is left for the free list.
+    #[cfg(any(debug_assertions, feature = "slab_poison"))]
+    unsafe fn poison(&self, object: *mut u8) {
+        let offset = mem::size_of::<FreeObject>();
+        ptr::write_bytes(object.offset(offset as isize),
+                         POISON_FREE,
+                         self.object_size - offset);
+    }
+
+    /// Fills a free object with the poison byte.
+    #[cfg(not(any(debug_assertions, feature = "slab_poison")))]
+    unsafe fn poison(&self, _object: *mut u8) {}
+
+    /// Checks that a free object wasn't written to.
+    #[cfg(any(debug_assertions, feature = "slab_poison"))]
+    unsafe fn check_poison(&self, object: *mut u8) {
+        for offset in mem::size_of::<FreeObject>()..self.object_size {
+            if *object.offset(offset as isize) != POISON_FREE {
+                panic!("Object 0x{:x} of slab cache {} was modified after being freed",
+                       object as usize,
+                       self.name);
+            }
+        }
+    }
+
+    /// Checks that a free object wasn't written to.
+    #[cfg(not(any(debug_assertions, feature = "slab_poison")))]
+    unsafe fn check_poison(&self, _object: *mut u8) {}
+}
+
+/// Pushes a slab onto the front of a list.
+unsafe fn push(list: &mut *mut SlabHeader, slab: *mut SlabHeader) {
+    (*slab).prev = ptr::null_mut();
+    (*slab).next = *list;
+    if !list.is_null() {
+        (**list).prev = slab;
+    }
+    *list = slab;
+}
+
+/// Removes a slab from a list.
+unsafe fn remove(list: &mut *mut SlabHeader, slab: *mut SlabHeader) {
+    if (*slab).prev.is_null() {
+        *list = (*slab).next;
+    } else {
+        (*(*slab).prev).next = (*slab).next;
+    }
+    if !(*slab).next.is_null() {
+        (*(*slab).next).prev = (*slab).prev;
+    }
+}
+
+/// Aligns an address upwards.
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
@@ -0,0 +1,118 @@
+use collections::Vec;
+use memory::{PAGE_SIZE, FrameAllocator};
+use memory::paging::{ActivePageTable, Page, PageIter, VirtualAddress, WRITABLE, NO_EXECUTE};
+
+/// The `Stack` type.
+///
+/// Represents a kernel stack with an unmapped guard page below it.
+#[derive(Debug)]
+pub struct Stack {
+    /// The top address, exclusive.
+    top: usize,
+
+    /// The bottom address.
+    bottom: usize,
+}
+
+/// The `Stack` implementation.
+impl Stack {
+    /// Gets the top address, exclusive.
+    ///
+    /// This is the initial stack pointer.
+    pub fn top(&self) -> usize {
+        self.top
+    }
+
+    /// Gets the bottom address.
+    pub fn bottom(&self) -> usize {
+        self.bottom
+    }
+}
+
+/// The `StackAllocator` type.
+///
+/// Hands out guarded stacks from a range of pages.
+pub struct StackAllocator {
+    /// The unused pages.
+    range: PageIter,
+
+    /// The addresses of the guard pages below the allocated stacks.
+    guard_pages: Vec<VirtualAddress>,
+}
+
+/// The `StackAllocator` implementation.
+impl StackAllocator {
+    /// Constructs a new `StackAllocator`.
+    pub fn new(range: PageIter) -> StackAllocator {
+        StackAllocator {
+            range: range,
+            guard_pages: Vec::new(),
+        }
+    }
+
+    /// Tests if an address is within the guard page of an allocated stack.
+    pub fn is_guard_page(&self, addr: VirtualAddress) -> bool {
+        self.guard_pages
+            .iter()
+            .any(|&guard_page| addr >= guard_page && addr < guard_page + PAGE_SIZE)
+    }
+
+    /// Allocates a stack of the specified size.
+    ///
+    /// The page below the stack is left unmapped, so
+    /// a stack overflow faults instead of corrupting memory.
+    pub fn alloc_stack<A>(&mut self,
+                          active_table: &mut ActivePageTable,
+                          frame_allocator: &mut A,
+                          size_in_pages: usize)
+                          -> Option<Stack>
+        where A: FrameAllocator
+    {
+        assert!(size_in_pages > 0, "Stacks need at least one page");
+
+        // Take the pages from a copy, so that nothing is lost on failure
+        let mut range = self.range.clone();
+        let guard_page = range.next();
+        let start_page = range.next();
+        let end_page = if size_in_pages == 1 {
+            start_page
+        } else {
+            range.nth(size_in_pages - 2)
+        };
+        match (guard_page, start_page, end_page) {
+            (Some(guard), Some(start), Some(end)) => {
+                self.range = range;
+                self.guard_pages.push(guard.address());
+
+                // Map the stack pages, but not the guard page
+                for page in Page::range_inclusive(start, end) {
+                    active_table.map(page, WRITABLE | NO_EXECUTE, frame_allocator);
+                }
+                Some(Stack {
+                    top: end.address() + PAGE_SIZE,
+                    bottom: start.address(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Deallocates a stack.
+    ///
+    /// Unmaps the stack pages and returns their frames to the allocator.
+    /// The address range isn't handed out again.
+    pub fn dealloc_stack<A>(&mut self,
+                            stack: Stack,
+                            active_table: &mut ActivePageTable,
+                            frame_allocator: &mut A)
+        where A: FrameAllocator
+    {
+        let start_page = Page::get_page_at_address(stack.bottom);
+        let end_page = Page::get_page_at_address(stack.top - 1);
+        for page in Page::range_inclusive(start_page, end_page) {
+            active_table.unmap(page, frame_allocator);
+        }
+        let guard_page = stack.bottom - PAGE_SIZE;
+        self.guard_pages.retain(|&addr| addr != guard_page);
+    }
+}
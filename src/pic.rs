@@ -0,0 +1,264 @@
+use spin::Mutex;
+use cpuio::{inb, outb};
+use interrupts::{self, InterruptFrame};
+use apic;
+
+/// The vector of the first IRQ of the master PIC.
+pub const PIC1_OFFSET: u8 = 32;
+
+/// The vector of the first IRQ of the slave PIC.
+pub const PIC2_OFFSET: u8 = PIC1_OFFSET + 8;
+
+/// The number of IRQ lines.
+pub const IRQ_COUNT: usize = 16;
+
+/// The IRQ line the slave PIC is cascaded to.
+const CASCADE_LINE: u8 = 2;
+
+/// The initialization command.
+const CMD_INIT: u8 = 0x11;
+
+/// The end of interrupt command.
+const CMD_END_OF_INTERRUPT: u8 = 0x20;
+
+/// The read in-service register command.
+const CMD_READ_ISR: u8 = 0x0b;
+
+/// The 8086 mode flag.
+const MODE_8086: u8 = 0x01;
+
+/// The chained PICs.
+pub static PICS: Mutex<ChainedPics> = Mutex::new(ChainedPics {
+    pics: [Pic {
+               offset: PIC1_OFFSET,
+               command: 0x20,
+               data: 0x21,
+           },
+           Pic {
+               offset: PIC2_OFFSET,
+               command: 0xA0,
+               data: 0xA1,
+           }],
+});
+
+/// The registered IRQ handlers.
+static IRQ_HANDLERS: Mutex<[Option<IrqHandler>; IRQ_COUNT]> = Mutex::new([None; IRQ_COUNT]);
+
+/// The `IrqHandler` type.
+pub type IrqHandler = fn(&mut InterruptFrame);
+
+/// The `Pic` type.
+///
+/// Represents a single 8259 PIC.
+struct Pic {
+    /// The vector of the first IRQ.
+    offset: u8,
+
+    /// The command port.
+    command: u16,
+
+    /// The data port.
+    data: u16,
+}
+
+/// The `Pic` implementation.
+impl Pic {
+    /// Tests if the PIC handles the specified vector.
+    fn handles(&self, vector: u8) -> bool {
+        vector >= self.offset && vector < self.offset + 8
+    }
+
+    /// Sends a command.
+    fn send_command(&self, command: u8) {
+        unsafe {
+            outb(command, self.command);
+        }
+    }
+
+    /// Sends a data byte.
+    fn send_data(&self, data: u8) {
+        unsafe {
+            outb(data, self.data);
+        }
+    }
+
+    /// Reads the in-service register.
+    fn read_isr(&self) -> u8 {
+        self.send_command(CMD_READ_ISR);
+        unsafe { inb(self.command) }
+    }
+
+    /// Reads the interrupt mask.
+    fn read_mask(&self) -> u8 {
+        unsafe { inb(self.data) }
+    }
+
+    /// Writes the interrupt mask.
+    fn write_mask(&self, mask: u8) {
+        self.send_data(mask);
+    }
+}
+
+/// The `ChainedPics` type.
+///
+/// Represents the master and the slave PIC.
+pub struct ChainedPics {
+    /// The master and the slave PIC.
+    pics: [Pic; 2],
+}
+
+/// The `ChainedPics` implementation.
+impl ChainedPics {
+    /// Initializes the PICs.
+    ///
+    /// Remaps the IRQs to the vectors behind the CPU exceptions
+    /// and masks every line except for the cascade line.
+    pub fn init(&mut self) {
+        // Waits for the PICs to process a command
+        let wait = || unsafe { outb(0, 0x80) };
+
+        // Start the initialization sequence
+        self.pics[0].send_command(CMD_INIT);
+        wait();
+        self.pics[1].send_command(CMD_INIT);
+        wait();
+
+        // Set the vector offsets
+        self.pics[0].send_data(self.pics[0].offset);
+        wait();
+        self.pics[1].send_data(self.pics[1].offset);
+        wait();
+
+        // Tell the PICs how they're chained
+        self.pics[0].send_data(1 << CASCADE_LINE);
+        wait();
+        self.pics[1].send_data(CASCADE_LINE);
+        wait();
+
+        // Set the mode
+        self.pics[0].send_data(MODE_8086);
+        wait();
+        self.pics[1].send_data(MODE_8086);
+        wait();
+
+        // Mask every line except for the cascade line
+        self.pics[0].write_mask(!(1 << CASCADE_LINE));
+        self.pics[1].write_mask(0xff);
+    }
+
+    /// Masks the specified IRQ line.
+    pub fn mask(&mut self, line: u8) {
+        let pic = &self.pics[(line / 8) as usize];
+        let mask = pic.read_mask() | 1 << (line % 8);
+        pic.write_mask(mask);
+    }
+
+    /// Unmasks the specified IRQ line.
+    pub fn unmask(&mut self, line: u8) {
+        let pic = &self.pics[(line / 8) as usize];
+        let mask = pic.read_mask() & !(1 << (line % 8));
+        pic.write_mask(mask);
+    }
+
+    /// Masks every IRQ line.
+    pub fn disable(&mut self) {
+        self.pics[0].write_mask(0xff);
+        self.pics[1].write_mask(0xff);
+    }
+
+    /// Tests if an IRQ line is currently being serviced.
+    ///
+    /// Spurious IRQs on line 7 and 15 aren't.
+    pub fn is_in_service(&self, line: u8) -> bool {
+        let pic = &self.pics[(line / 8) as usize];
+        pic.read_isr() & 1 << (line % 8) != 0
+    }
+
+    /// Sends the end of interrupt command for the specified IRQ line.
+    pub fn send_eoi(&mut self, line: u8) {
+        if line >= 8 {
+            self.pics[1].send_command(CMD_END_OF_INTERRUPT);
+        }
+        self.pics[0].send_command(CMD_END_OF_INTERRUPT);
+    }
+
+    /// Gets the IRQ line of the specified vector.
+    pub fn line_of(&self, vector: u8) -> Option<u8> {
+        if self.pics.iter().any(|pic| pic.handles(vector)) {
+            Some(vector - PIC1_OFFSET)
+        } else {
+            None
+        }
+    }
+}
+
+/// Initializes the PICs and routes their IRQs to the registered IRQ handlers.
+pub fn init() {
+    PICS.lock().init();
+    for line in 0..IRQ_COUNT as u8 {
+        interrupts::register_handler(PIC1_OFFSET + line, irq_dispatch);
+    }
+}
+
+/// Registers the handler of the specified IRQ line and unmasks the line.
+///
+/// Once the APICs are enabled, the line is routed through the I/O APIC
+/// to the same vector on the boot processor instead.
+/// The end of interrupt command is sent after the handler returns.
+pub fn register_irq_handler(line: u8, handler: IrqHandler) {
+    interrupts::without_interrupts(|| {
+        IRQ_HANDLERS.lock()[line as usize] = Some(handler);
+        if apic::is_enabled() {
+            apic::route_irq(line, PIC1_OFFSET + line, apic::local_apic_id());
+        } else {
+            PICS.lock().unmask(line);
+        }
+    });
+}
+
+/// Unregisters the handler of the specified IRQ line and masks the line.
+pub fn unregister_irq_handler(line: u8) {
+    interrupts::without_interrupts(|| {
+        if apic::is_enabled() {
+            apic::mask_irq(line);
+        } else {
+            PICS.lock().mask(line);
+        }
+        IRQ_HANDLERS.lock()[line as usize] = None;
+    });
+}
+
+/// Dispatches an IRQ to its registered handler.
+fn irq_dispatch(frame: &mut InterruptFrame) {
+    let line = PICS.lock().line_of(frame.vector as u8).unwrap();
+
+    if apic::is_enabled() {
+        let handler = IRQ_HANDLERS.lock()[line as usize];
+        match handler {
+            Some(handler) => {
+                handler(frame);
+                apic::send_eoi();
+            }
+            // Only lines with a handler are routed through the I/O APIC, so this is
+            // a spurious IRQ of the disabled PIC, which the local APIC never saw
+            None if line == 7 || line == 15 => (),
+            None => apic::send_eoi(),
+        }
+        return;
+    }
+
+    // Ignore spurious IRQs
+    if (line == 7 || line == 15) && !PICS.lock().is_in_service(line) {
+        if line == 15 {
+            // The master PIC doesn't know the IRQ was spurious
+            PICS.lock().send_eoi(CASCADE_LINE);
+        }
+        return;
+    }
+
+    let handler = IRQ_HANDLERS.lock()[line as usize];
+    if let Some(handler) = handler {
+        handler(frame);
+    }
+    PICS.lock().send_eoi(line);
+}
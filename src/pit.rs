@@ -0,0 +1,48 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use cpuio::outb;
+use interrupts::InterruptFrame;
+use pic;
+
+/// The input frequency of the PIT in Hz.
+const PIT_FREQUENCY: u32 = 1193182;
+
+/// The number of timer ticks per second.
+pub const TICK_RATE: u32 = 1000;
+
+/// The channel 0 data port.
+const CHANNEL0: u16 = 0x40;
+
+/// The mode/command port.
+const COMMAND: u16 = 0x43;
+
+/// Channel 0, low byte then high byte, rate generator.
+const MODE_RATE_GENERATOR: u8 = 0x34;
+
+/// The number of timer ticks since `init`.
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Programs channel 0 of the PIT to fire IRQ 0 at `TICK_RATE`.
+pub fn init() {
+    let divisor = PIT_FREQUENCY / TICK_RATE;
+    unsafe {
+        outb(MODE_RATE_GENERATOR, COMMAND);
+        outb(divisor as u8, CHANNEL0);
+        outb((divisor >> 8) as u8, CHANNEL0);
+    }
+    pic::register_irq_handler(0, timer_handler);
+}
+
+/// Gets the number of timer ticks since `init`.
+pub fn ticks() -> usize {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Gets the number of milliseconds since `init`.
+pub fn uptime_ms() -> usize {
+    ticks() * 1000 / TICK_RATE as usize
+}
+
+/// Handles a timer tick.
+fn timer_handler(_frame: &mut InterruptFrame) {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
@@ -0,0 +1,79 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The capacity of a ring buffer, must be a power of two.
+pub const RING_BUFFER_SIZE: usize = 256;
+
+/// The `RingBuffer` type.
+///
+/// A lock-free byte queue for a single producer and a single consumer,
+/// usually an interrupt handler and the code reading its data.
+pub struct RingBuffer {
+    /// The bytes.
+    data: UnsafeCell<[u8; RING_BUFFER_SIZE]>,
+
+    /// The number of bytes pushed so far, only written by the producer.
+    head: AtomicUsize,
+
+    /// The number of bytes popped so far, only written by the consumer.
+    tail: AtomicUsize,
+}
+
+/// The `Sync` implementation for `RingBuffer`.
+///
+/// The producer only writes slots the consumer doesn't read yet, and vice versa.
+unsafe impl Sync for RingBuffer {}
+
+/// The `RingBuffer` implementation.
+impl RingBuffer {
+    /// Constructs a new empty `RingBuffer`.
+    pub const fn new() -> RingBuffer {
+        RingBuffer {
+            data: UnsafeCell::new([0; RING_BUFFER_SIZE]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pushes a byte.
+    ///
+    /// Returns false and drops the byte if the buffer is full.
+    /// Must only be called by the producer.
+    pub fn push(&self, byte: u8) -> bool {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head.wrapping_sub(tail) == RING_BUFFER_SIZE {
+            return false;
+        }
+        unsafe {
+            (*self.data.get())[head % RING_BUFFER_SIZE] = byte;
+        }
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Pops the oldest byte.
+    ///
+    /// Must only be called by the consumer.
+    pub fn pop(&self) -> Option<u8> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let byte = unsafe { (*self.data.get())[tail % RING_BUFFER_SIZE] };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Some(byte)
+    }
+
+    /// Gets the number of buffered bytes.
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        self.head.load(Ordering::Acquire).wrapping_sub(tail)
+    }
+
+    /// Tests if the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -1,5 +1,9 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use spin::Mutex;
 use cpuio::{inb, outb};
+use interrupts::{self, InterruptFrame};
+use pic::{self, PIC1_OFFSET};
+use ring_buffer::RingBuffer;
 
 macro_rules! serial_data { ($port:expr) => ($port + 0) }
 macro_rules! serial_ier { ($port:expr) => ($port + 1) }
@@ -7,29 +11,239 @@ macro_rules! serial_fifo { ($port:expr) => ($port + 2) }
 macro_rules! serial_line { ($port:expr) => ($port + 3) }
 macro_rules! serial_modem { ($port:expr) => ($port + 4) }
 macro_rules! serial_line_status { ($port:expr) => ($port + 5) }
+macro_rules! serial_iir { ($port:expr) => ($port + 2) }
+macro_rules! serial_modem_status { ($port:expr) => ($port + 6) }
+macro_rules! serial_scratch { ($port:expr) => ($port + 7) }
+
+/// The number of line status polls before a write gives up.
+const WRITE_TIMEOUT: usize = 100_000;
+
+/// The modem control value used in normal operation, DTR, RTS and OUT2.
+const MODEM_NORMAL: u8 = 0x0b;
+
+/// The modem control value of the loopback self-test, RTS, OUT1, OUT2 and loopback.
+const MODEM_LOOPBACK: u8 = 0x1e;
+
+/// The `SerialError` type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SerialError {
+    /// No UART responds at the port.
+    NotPresent,
+
+    /// The UART didn't become ready to transmit in time.
+    Timeout,
+
+    /// The baud rate doesn't divide `MAX_BAUD_RATE`.
+    UnsupportedBaudRate,
+}
+
+/// The `UartModel` type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UartModel {
+    /// The original UART, without a scratch register.
+    Uart8250,
+
+    /// Like the 8250, with a scratch register.
+    Uart16450,
+
+    /// The first UART with a FIFO, which doesn't work.
+    Uart16550,
+
+    /// A UART with a working 16 byte FIFO.
+    Uart16550A,
+
+    /// A UART with a 64 byte FIFO.
+    Uart16750,
+}
+
+/// The frequency of the UART clock divided by 16, the highest baud rate.
+pub const MAX_BAUD_RATE: u32 = 115200;
+
+/// The default configuration, 38400 baud 8N1.
+pub const DEFAULT_CONFIG: SerialConfig = SerialConfig {
+    baud_rate: 38400,
+    data_bits: DataBits::Eight,
+    parity: Parity::None,
+    stop_bits: StopBits::One,
+    fifo_trigger: FifoTrigger::Bytes14,
+    flow_control: FlowControl::None,
+};
+
+/// The number of data bits per character.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DataBits {
+    Five,
+    Six,
+    Seven,
+    Eight,
+}
+
+/// The parity bit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+    Mark,
+    Space,
+}
+
+/// The number of stop bits.
+///
+/// With five data bits, `Two` means one and a half stop bits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+/// The number of received bytes in the FIFO that raises an interrupt.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FifoTrigger {
+    Bytes1,
+    Bytes4,
+    Bytes8,
+    Bytes14,
+}
+
+/// The flow control.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FlowControl {
+    None,
+
+    /// Hardware flow control, bytes are only sent while CTS is asserted.
+    RtsCts,
+}
+
+/// Tests if a baud rate divides `MAX_BAUD_RATE`.
+pub fn is_baud_rate_supported(baud_rate: u32) -> bool {
+    baud_rate != 0 && MAX_BAUD_RATE % baud_rate == 0
+}
+
+/// The `SerialConfig` type.
+///
+/// Holds the line settings of a serial port.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SerialConfig {
+    /// The baud rate, must divide `MAX_BAUD_RATE`.
+    pub baud_rate: u32,
+
+    /// The number of data bits.
+    pub data_bits: DataBits,
+
+    /// The parity bit.
+    pub parity: Parity,
+
+    /// The number of stop bits.
+    pub stop_bits: StopBits,
+
+    /// The FIFO trigger level.
+    pub fifo_trigger: FifoTrigger,
+
+    /// The flow control.
+    pub flow_control: FlowControl,
+}
+
+/// The `Default` implementation for `SerialConfig`.
+impl Default for SerialConfig {
+    /// Gets the default configuration.
+    fn default() -> SerialConfig {
+        DEFAULT_CONFIG
+    }
+}
+
+/// The `SerialConfig` implementation.
+impl SerialConfig {
+    /// Gets the value of the line control register.
+    fn line_control(&self) -> u8 {
+        let data_bits = match self.data_bits {
+            DataBits::Five => 0b00,
+            DataBits::Six => 0b01,
+            DataBits::Seven => 0b10,
+            DataBits::Eight => 0b11,
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 0,
+            StopBits::Two => 1 << 2,
+        };
+        let parity = match self.parity {
+            Parity::None => 0b000 << 3,
+            Parity::Odd => 0b001 << 3,
+            Parity::Even => 0b011 << 3,
+            Parity::Mark => 0b101 << 3,
+            Parity::Space => 0b111 << 3,
+        };
+        data_bits | stop_bits | parity
+    }
+
+    /// Gets the value of the FIFO control register.
+    ///
+    /// Enables and clears both FIFOs.
+    fn fifo_control(&self) -> u8 {
+        let trigger = match self.fifo_trigger {
+            FifoTrigger::Bytes1 => 0b00,
+            FifoTrigger::Bytes4 => 0b01,
+            FifoTrigger::Bytes8 => 0b10,
+            FifoTrigger::Bytes14 => 0b11,
+        };
+        trigger << 6 | 0x07
+    }
+}
+
+/// The receiver of COM1.
+///
+/// Reads don't need the lock of the COM1 writer.
+pub static COM1_RECEIVER: SerialReceiver = SerialReceiver::new(0x3F8, 4);
+
+/// The receiver of COM2.
+///
+/// Reads don't need the lock of the COM2 writer.
+pub static COM2_RECEIVER: SerialReceiver = SerialReceiver::new(0x2F8, 3);
+
+/// The receiver of COM3.
+///
+/// Reads don't need the lock of the COM3 writer.
+pub static COM3_RECEIVER: SerialReceiver = SerialReceiver::new(0x3E8, 4);
+
+/// The receiver of COM4.
+///
+/// Reads don't need the lock of the COM4 writer.
+pub static COM4_RECEIVER: SerialReceiver = SerialReceiver::new(0x2E8, 3);
 
 /// A serial writer to COM1.
 pub static COM1: Mutex<SerialWriter> = Mutex::new(SerialWriter {
     port: 0x3F8,
     irq: 4,
+    receiver: &COM1_RECEIVER,
+    config: DEFAULT_CONFIG,
+    model: None,
 });
 
 /// A serial writer to COM2.
 pub static COM2: Mutex<SerialWriter> = Mutex::new(SerialWriter {
     port: 0x2F8,
     irq: 3,
+    receiver: &COM2_RECEIVER,
+    config: DEFAULT_CONFIG,
+    model: None,
 });
 
 /// A serial writer to COM3.
 pub static COM3: Mutex<SerialWriter> = Mutex::new(SerialWriter {
     port: 0x3E8,
     irq: 4,
+    receiver: &COM3_RECEIVER,
+    config: DEFAULT_CONFIG,
+    model: None,
 });
 
 /// A serial writer to COM4.
 pub static COM4: Mutex<SerialWriter> = Mutex::new(SerialWriter {
     port: 0x2E8,
     irq: 3,
+    receiver: &COM4_RECEIVER,
+    config: DEFAULT_CONFIG,
+    model: None,
 });
 
 /// The `SerialWriter` type.
@@ -39,6 +253,140 @@ pub struct SerialWriter {
 
     /// The IRQ.
     irq: u8,
+
+    /// The receiver filled by the IRQ handler.
+    receiver: &'static SerialReceiver,
+
+    /// The line settings.
+    config: SerialConfig,
+
+    /// The model, if the port has been probed.
+    model: Option<UartModel>,
+}
+
+/// The `SerialReceiver` type.
+///
+/// Holds the bytes received by a serial port while its
+/// receive interrupt is enabled.
+pub struct SerialReceiver {
+    /// The port.
+    port: u16,
+
+    /// The IRQ.
+    irq: u8,
+
+    /// Whether a UART responds at the port.
+    ///
+    /// Assumed until the port is probed.
+    present: AtomicBool,
+
+    /// Whether the receive interrupt is enabled.
+    enabled: AtomicBool,
+
+    /// The received bytes.
+    buffer: RingBuffer,
+
+    /// The number of bytes dropped because the buffer was full.
+    dropped: AtomicUsize,
+}
+
+/// The `SerialReceiver` implementation.
+impl SerialReceiver {
+    /// Constructs a new `SerialReceiver`.
+    const fn new(port: u16, irq: u8) -> SerialReceiver {
+        SerialReceiver {
+            port: port,
+            irq: irq,
+            present: AtomicBool::new(true),
+            enabled: AtomicBool::new(false),
+            buffer: RingBuffer::new(),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Tests if the UART holds a received byte.
+    fn contains_data(&self) -> bool {
+        unsafe { (inb(serial_line_status!(self.port)) & 1) > 0 }
+    }
+
+    /// Gets the number of received bytes dropped because the buffer was full.
+    pub fn dropped_bytes(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Reads a byte if one is available.
+    pub fn try_read_byte(&self) -> Option<u8> {
+        if !self.present.load(Ordering::SeqCst) {
+            None
+        } else if self.enabled.load(Ordering::SeqCst) {
+            self.buffer.pop()
+        } else if self.contains_data() {
+            Some(unsafe { inb(serial_data!(self.port)) })
+        } else {
+            None
+        }
+    }
+
+    /// Reads a byte.
+    ///
+    /// Sleeps until the next interrupt while the receive buffer is empty.
+    /// Falls back to polling if the receive interrupt or interrupts in
+    /// general are disabled, since no interrupt would wake the CPU.
+    /// Fails with `SerialError::NotPresent` if the port is missing.
+    pub fn read_byte(&self) -> Result<u8, SerialError> {
+        if !self.present.load(Ordering::SeqCst) {
+            return Err(SerialError::NotPresent);
+        }
+        if !self.enabled.load(Ordering::SeqCst) || !interrupts::are_enabled() {
+            while !self.contains_data() {}
+            return Ok(unsafe { inb(serial_data!(self.port)) });
+        }
+        loop {
+            // Check the buffer with interrupts disabled,
+            // so no byte arrives between the check and the halt
+            interrupts::disable();
+            if let Some(byte) = self.buffer.pop() {
+                interrupts::enable();
+                return Ok(byte);
+            }
+            interrupts::enable_and_hlt();
+        }
+    }
+
+    /// Reads a char.
+    #[inline(always)]
+    pub fn read_char(&self) -> Result<char, SerialError> {
+        self.read_byte().map(|byte| byte as char)
+    }
+
+    /// Moves the bytes waiting in the UART into the buffer.
+    fn receive(&self) {
+        unsafe {
+            // Bit 0 of the interrupt identification register is clear
+            // while the port has a pending interrupt
+            if inb(serial_iir!(self.port)) & 1 != 0 {
+                return;
+            }
+            while inb(serial_line_status!(self.port)) & 1 != 0 {
+                if !self.buffer.push(inb(serial_data!(self.port))) {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+/// Handles the IRQ shared by COM1 and COM3, or by COM2 and COM4.
+///
+/// Doesn't lock the serial writers, so a writer interrupted
+/// while holding its lock can't deadlock the handler.
+fn serial_irq_handler(frame: &mut InterruptFrame) {
+    let irq = frame.vector as u8 - PIC1_OFFSET;
+    for receiver in [&COM1_RECEIVER, &COM2_RECEIVER, &COM3_RECEIVER, &COM4_RECEIVER].iter() {
+        if receiver.irq == irq && receiver.enabled.load(Ordering::SeqCst) {
+            receiver.receive();
+        }
+    }
 }
 
 /// The `::core::fmt::Write` implementation for `SerialWriter`.
@@ -46,52 +394,185 @@ impl ::core::fmt::Write for SerialWriter {
     /// Writes a string.
     #[inline(always)]
     fn write_str(&mut self, string: &str) -> ::core::fmt::Result {
-        for byte in string.bytes() {
-            self.write_byte(byte)
-        }
-        Ok(())
+        SerialWriter::write_str(self, string).map_err(|_| ::core::fmt::Error)
     }
 }
 
 /// The `SerialWriter` implementation.
 impl SerialWriter {
-    /// Initializes the serial writer.
-    #[inline(always)]
-    pub fn init(&self) {
+    /// Initializes the serial writer with the specified line settings.
+    ///
+    /// Programs the divisor and the line settings before probing the port,
+    /// since the self-test needs a working divisor, and stops there if no
+    /// UART responds. Disables the interrupts of the port and asserts
+    /// DTR, RTS and OUT2. The receive interrupt has to be enabled again
+    /// afterwards. Fails without touching the port if the baud rate is
+    /// unsupported.
+    pub fn init(&mut self, config: SerialConfig) -> Result<UartModel, SerialError> {
+        if !is_baud_rate_supported(config.baud_rate) {
+            return Err(SerialError::UnsupportedBaudRate);
+        }
+        self.disable_rx_interrupt();
+        self.config = config;
+        try!(self.set_baud_rate(config.baud_rate));
+        self.set_line(config.data_bits, config.parity, config.stop_bits);
+        let model = try!(self.probe());
+        self.enable_buffer(config.fifo_trigger);
+        unsafe {
+            outb(MODEM_NORMAL, serial_modem!(self.port));
+        }
+        Ok(model)
+    }
+
+    /// Probes the port for a UART and detects its model.
+    ///
+    /// Checks the scratch register and runs a loopback self-test,
+    /// then looks at the FIFO bits of the interrupt identification register.
+    /// Writers to missing ports fail with `SerialError::NotPresent`.
+    pub fn probe(&mut self) -> Result<UartModel, SerialError> {
+        let present = self.loopback_test();
+        self.receiver.present.store(present, Ordering::SeqCst);
+        if !present {
+            self.model = None;
+            return Err(SerialError::NotPresent);
+        }
+
+        let model = unsafe {
+            // Try to enable the FIFOs, including the 64 byte FIFO of the 16750,
+            // which only takes the 64 byte bit while DLAB is set
+            let line = inb(serial_line!(self.port));
+            outb(line | 0x80, serial_line!(self.port));
+            outb(0xe7, serial_fifo!(self.port));
+            outb(line, serial_line!(self.port));
+            let iir = inb(serial_iir!(self.port));
+            match iir >> 6 {
+                0b11 if iir & 0x20 != 0 => UartModel::Uart16750,
+                0b11 => UartModel::Uart16550A,
+                0b10 => UartModel::Uart16550,
+                _ if self.has_scratch_register() => UartModel::Uart16450,
+                _ => UartModel::Uart8250,
+            }
+        };
+        unsafe {
+            outb(self.config.fifo_control(), serial_fifo!(self.port));
+        }
+        self.model = Some(model);
+        Ok(model)
+    }
+
+    /// Tests if the scratch register keeps the values written to it.
+    ///
+    /// The 8250 has no scratch register.
+    fn has_scratch_register(&self) -> bool {
+        [0x55, 0xaa].iter().all(|&value| unsafe {
+            outb(value, serial_scratch!(self.port));
+            inb(serial_scratch!(self.port)) == value
+        })
+    }
+
+    /// Tests if bytes sent in loopback mode are received.
+    ///
+    /// Nothing leaves the port during the test.
+    fn loopback_test(&self) -> bool {
         unsafe {
-            outb(0x00, serial_data!(self.port));
-            outb(0x80, serial_line!(self.port));
-            outb(0x03, serial_data!(self.port));
+            // An absent port floats high on every register
+            if inb(serial_line_status!(self.port)) == 0xff {
+                return false;
+            }
+
+            let ier = inb(serial_ier!(self.port));
             outb(0x00, serial_ier!(self.port));
-            outb(0x03, serial_line!(self.port));
-            outb(0xc7, serial_fifo!(self.port));
-            outb(0x0b, serial_modem!(self.port));
+            outb(MODEM_LOOPBACK, serial_modem!(self.port));
+
+            // Drain stale bytes before sending the test byte
+            let mut spins = 0;
+            while inb(serial_line_status!(self.port)) & 1 != 0 && spins < 64 {
+                inb(serial_data!(self.port));
+                spins += 1;
+            }
+            outb(0xae, serial_data!(self.port));
+            let mut spins = 0;
+            while inb(serial_line_status!(self.port)) & 1 == 0 && spins < WRITE_TIMEOUT {
+                spins += 1;
+            }
+            let passed = spins < WRITE_TIMEOUT && inb(serial_data!(self.port)) == 0xae;
+
+            outb(MODEM_NORMAL, serial_modem!(self.port));
+            outb(ier, serial_ier!(self.port));
+            passed
         }
     }
 
+    /// Gets the model, if the port has been probed and a UART responded.
+    pub fn model(&self) -> Option<UartModel> {
+        self.model
+    }
+
+    /// Tests if a UART responds at the port.
+    ///
+    /// Always true until the port is probed. False after a write timed out.
+    pub fn is_present(&self) -> bool {
+        self.receiver.present.load(Ordering::SeqCst)
+    }
+
+    /// Gets the line settings.
+    pub fn config(&self) -> SerialConfig {
+        self.config
+    }
+
     /// Sets the baud rate.
-    #[inline(always)]
-    pub fn set_baud_rate(&self, divisor: u32) {
+    ///
+    /// Fails with `SerialError::UnsupportedBaudRate`
+    /// if the baud rate doesn't divide `MAX_BAUD_RATE`.
+    pub fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), SerialError> {
+        if !is_baud_rate_supported(baud_rate) {
+            return Err(SerialError::UnsupportedBaudRate);
+        }
+        let divisor = MAX_BAUD_RATE / baud_rate;
+        unsafe {
+            // The divisor latch registers overlay the data and
+            // the interrupt enable registers while DLAB is set
+            let line = inb(serial_line!(self.port));
+            outb(line | 0x80, serial_line!(self.port));
+            outb(divisor as u8, serial_data!(self.port));
+            outb((divisor >> 8) as u8, serial_ier!(self.port));
+            outb(line & !0x80, serial_line!(self.port));
+        }
+        self.config.baud_rate = baud_rate;
+        Ok(())
+    }
+
+    /// Sets the data bits, the parity and the stop bits.
+    pub fn set_line(&mut self, data_bits: DataBits, parity: Parity, stop_bits: StopBits) {
+        self.config.data_bits = data_bits;
+        self.config.parity = parity;
+        self.config.stop_bits = stop_bits;
         unsafe {
-            outb(0x80, serial_line!(self.port));
-            outb(((divisor >> 8) & 0x00FF) as u8, serial_data!(self.port));
-            outb((divisor & 0x00FF) as u8, serial_data!(self.port));
+            outb(self.config.line_control(), serial_line!(self.port));
         }
     }
 
-    /// Sets the line.
-    #[inline(always)]
-    pub fn set_line(&self) {
+    /// Enables and clears the FIFOs with the specified trigger level.
+    pub fn enable_buffer(&mut self, fifo_trigger: FifoTrigger) {
+        self.config.fifo_trigger = fifo_trigger;
         unsafe {
-            outb(0x03, serial_line!(self.port));
+            outb(self.config.fifo_control(), serial_fifo!(self.port));
         }
     }
 
-    /// Enables the buffer.
+    /// Sets the flow control.
+    pub fn set_flow_control(&mut self, flow_control: FlowControl) {
+        self.config.flow_control = flow_control;
+    }
+
+    /// Tests if the other end is ready to receive.
+    ///
+    /// Always true without hardware flow control.
     #[inline(always)]
-    pub fn enable_buffer(&self) {
-        unsafe {
-            outb(0xc7, serial_fifo!(self.port));
+    pub fn clear_to_send(&self) -> bool {
+        match self.config.flow_control {
+            FlowControl::None => true,
+            FlowControl::RtsCts => unsafe { inb(serial_modem_status!(self.port)) & 0x10 != 0 },
         }
     }
 
@@ -108,32 +589,63 @@ impl SerialWriter {
     }
 
     /// Writes a byte.
+    ///
+    /// Gives up if the UART isn't ready to transmit after `WRITE_TIMEOUT` polls.
+    /// The port is treated as missing from then on, until it's initialized again.
     #[inline(always)]
-    pub fn write_byte(&self, byte: u8) {
-        while !self.buffer_is_empty() {}
+    pub fn write_byte(&self, byte: u8) -> Result<(), SerialError> {
+        if !self.receiver.present.load(Ordering::SeqCst) {
+            return Err(SerialError::NotPresent);
+        }
+        let mut spins = 0;
+        while !self.buffer_is_empty() || !self.clear_to_send() {
+            spins += 1;
+            if spins == WRITE_TIMEOUT {
+                self.receiver.present.store(false, Ordering::SeqCst);
+                return Err(SerialError::Timeout);
+            }
+        }
         unsafe {
             outb(byte, self.port);
         }
+        Ok(())
     }
 
     /// Writes a string.
     #[inline(always)]
-    pub fn write_str(&self, string: &str) {
+    pub fn write_str(&self, string: &str) -> Result<(), SerialError> {
         for byte in string.bytes() {
-            self.write_byte(byte);
+            try!(self.write_byte(byte));
         }
+        Ok(())
     }
 
-    /// Reads a byte.
-    #[inline(always)]
-    pub fn read_byte(&self) -> u8 {
-        while !self.buffer_contains_data() {}
-        unsafe { inb(self.port) }
+    /// Enables the receive interrupt.
+    ///
+    /// Received bytes are buffered by the IRQ handler from now on.
+    /// Does nothing if the port is missing.
+    pub fn enable_rx_interrupt(&self) {
+        if !self.receiver.present.load(Ordering::SeqCst) {
+            return;
+        }
+        interrupts::without_interrupts(|| {
+            self.receiver.enabled.store(true, Ordering::SeqCst);
+            pic::register_irq_handler(self.irq, serial_irq_handler);
+            unsafe {
+                outb(0x01, serial_ier!(self.port));
+            }
+        });
     }
 
-    /// Reads a char.
-    #[inline(always)]
-    pub fn read_char(&self) -> char {
-        self.read_byte() as char
+    /// Disables the receive interrupt.
+    ///
+    /// The IRQ line stays unmasked, since it's shared with another port.
+    pub fn disable_rx_interrupt(&self) {
+        interrupts::without_interrupts(|| {
+            unsafe {
+                outb(0x00, serial_ier!(self.port));
+            }
+            self.receiver.enabled.store(false, Ordering::SeqCst);
+        });
     }
 }
@@ -0,0 +1,179 @@
+/// The maximum number of CSI parameters, further ones are ignored.
+const MAX_PARAMS: usize = 8;
+
+/// The escape byte starting every escape sequence.
+const ESCAPE: u8 = 0x1b;
+
+/// The `State` type.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum State {
+    /// Printing bytes.
+    Ground,
+
+    /// After an escape byte.
+    Escape,
+
+    /// Inside a control sequence, after `ESC [`.
+    Csi,
+}
+
+/// The `Csi` type.
+///
+/// Represents a control sequence like `ESC [ 1 ; 31 m`.
+#[derive(Copy, Clone)]
+pub struct Csi {
+    /// The parameters.
+    params: [u16; MAX_PARAMS],
+
+    /// The number of parameters.
+    count: usize,
+
+    /// Whether the sequence has a private marker like `?`.
+    pub private: bool,
+
+    /// The final byte, which selects the function.
+    pub command: u8,
+}
+
+/// The `Csi` implementation.
+impl Csi {
+    /// Gets a parameter.
+    ///
+    /// Missing and zero parameters default to the specified value.
+    pub fn param(&self, index: usize, default: u16) -> u16 {
+        match self.params().get(index) {
+            Some(&value) if value != 0 => value,
+            _ => default,
+        }
+    }
+
+    /// Gets the parameters.
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.count]
+    }
+}
+
+/// The `Action` type.
+///
+/// Represents what a byte fed to the parser amounts to.
+#[derive(Copy, Clone)]
+pub enum Action {
+    /// A byte to print, including control characters like `\n`.
+    Print(u8),
+
+    /// A complete control sequence.
+    Csi(Csi),
+
+    /// A complete escape sequence without parameters, holds its final byte.
+    Escape(u8),
+}
+
+/// The `Parser` type.
+///
+/// Splits a byte stream into printable bytes and ANSI/VT100 escape sequences.
+pub struct Parser {
+    /// The state.
+    state: State,
+
+    /// The control sequence being parsed.
+    csi: Csi,
+}
+
+/// The `Parser` implementation.
+impl Parser {
+    /// Constructs a new `Parser`.
+    pub const fn new() -> Parser {
+        Parser {
+            state: State::Ground,
+            csi: Csi {
+                params: [0; MAX_PARAMS],
+                count: 0,
+                private: false,
+                command: 0,
+            },
+        }
+    }
+
+    /// Feeds a byte to the parser.
+    ///
+    /// Returns an action once a byte or a sequence is complete.
+    pub fn advance(&mut self, byte: u8) -> Option<Action> {
+        match self.state {
+            State::Ground => {
+                if byte == ESCAPE {
+                    self.state = State::Escape;
+                    None
+                } else {
+                    Some(Action::Print(byte))
+                }
+            }
+            State::Escape => {
+                match byte {
+                    b'[' => {
+                        self.state = State::Csi;
+                        self.csi.count = 0;
+                        self.csi.private = false;
+                        None
+                    }
+                    ESCAPE => None,
+                    _ => {
+                        self.state = State::Ground;
+                        Some(Action::Escape(byte))
+                    }
+                }
+            }
+            State::Csi => self.advance_csi(byte),
+        }
+    }
+
+    /// Feeds a byte of a control sequence to the parser.
+    fn advance_csi(&mut self, byte: u8) -> Option<Action> {
+        match byte {
+            b'0'...b'9' => {
+                if self.csi.count == 0 {
+                    self.csi.params[0] = 0;
+                    self.csi.count = 1;
+                }
+                let index = self.csi.count - 1;
+                let digit = (byte - b'0') as u16;
+                let param = &mut self.csi.params[index];
+                *param = param.saturating_mul(10).saturating_add(digit);
+                None
+            }
+            b';' => {
+                if self.csi.count == 0 {
+                    // An empty first parameter
+                    self.csi.params[0] = 0;
+                    self.csi.count = 1;
+                }
+                if self.csi.count < MAX_PARAMS {
+                    self.csi.params[self.csi.count] = 0;
+                    self.csi.count += 1;
+                }
+                None
+            }
+            b'<'...b'?' => {
+                self.csi.private = true;
+                None
+            }
+            // Intermediate bytes aren't used by any supported sequence
+            0x20...0x2f => None,
+            0x40...0x7e => {
+                self.state = State::Ground;
+                self.csi.command = byte;
+                Some(Action::Csi(self.csi))
+            }
+            ESCAPE => {
+                // Abort the sequence and start a new one
+                self.state = State::Escape;
+                None
+            }
+            // Control characters are executed in the middle of a sequence
+            0x00...0x1f => Some(Action::Print(byte)),
+            _ => {
+                self.state = State::Ground;
+                None
+            }
+        }
+    }
+}
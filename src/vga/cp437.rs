@@ -0,0 +1,131 @@
+/// The glyph shown for characters without a CP437 glyph, a small square.
+pub const REPLACEMENT_GLYPH: u8 = 0xfe;
+
+/// The Unicode replacement character, returned for invalid UTF-8.
+const REPLACEMENT_CHARACTER: char = '\u{fffd}';
+
+/// The characters of the CP437 glyphs 0x01 to 0x1f.
+///
+/// Only reachable through their Unicode code points,
+/// since the bytes themselves are control characters.
+static LOW_GLYPHS: [char; 31] = ['☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀',
+                                 '♪', '♫', '☼', '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑',
+                                 '↓', '→', '←', '∟', '↔', '▲', '▼'];
+
+/// The characters of the CP437 glyphs 0x80 to 0xff.
+static HIGH_GLYPHS: [char; 128] = ['Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï',
+                                   'î', 'ì', 'Ä', 'Å', 'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù',
+                                   'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', 'á', 'í', 'ó', 'ú',
+                                   'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+                                   '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗',
+                                   '╝', '╜', '╛', '┐', '└', '┴', '┬', '├', '─', '┼', '╞', '╟',
+                                   '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', '╨', '╤', '╥', '╙',
+                                   '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+                                   'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ',
+                                   '∞', 'φ', 'ε', '∩', '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈',
+                                   '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}'];
+
+/// Look-alike characters drawn with the glyph of another character.
+static ALIASES: [(char, u8); 6] = [('β', 0xe1), ('μ', 0xe6), ('∈', 0xee), ('ϕ', 0xed),
+                                   ('∅', 0xed), ('⌂', 0x7f)];
+
+/// Gets the CP437 glyph of a character.
+///
+/// Returns `REPLACEMENT_GLYPH` if there is none.
+pub fn from_char(c: char) -> u8 {
+    if c >= ' ' && c <= '~' {
+        return c as u8;
+    }
+    if let Some(index) = HIGH_GLYPHS.iter().position(|&glyph| glyph == c) {
+        return 0x80 + index as u8;
+    }
+    if let Some(index) = LOW_GLYPHS.iter().position(|&glyph| glyph == c) {
+        return 0x01 + index as u8;
+    }
+    ALIASES.iter()
+        .find(|&&(alias, _)| alias == c)
+        .map_or(REPLACEMENT_GLYPH, |&(_, glyph)| glyph)
+}
+
+/// The `Utf8Decoder` type.
+///
+/// Decodes the non-ASCII bytes of a UTF-8 stream one at a time.
+pub struct Utf8Decoder {
+    /// The code point decoded so far.
+    code_point: u32,
+
+    /// The number of continuation bytes still expected.
+    remaining: u8,
+
+    /// The smallest code point allowed for the sequence length,
+    /// used to reject overlong encodings.
+    min: u32,
+}
+
+/// The `Utf8Decoder` implementation.
+impl Utf8Decoder {
+    /// Constructs a new `Utf8Decoder`.
+    pub const fn new() -> Utf8Decoder {
+        Utf8Decoder {
+            code_point: 0,
+            remaining: 0,
+            min: 0,
+        }
+    }
+
+    /// Tests if a sequence has been started but isn't complete.
+    pub fn is_pending(&self) -> bool {
+        self.remaining != 0
+    }
+
+    /// Aborts the current sequence.
+    pub fn reset(&mut self) {
+        self.remaining = 0;
+    }
+
+    /// Feeds a non-ASCII byte to the decoder.
+    ///
+    /// Returns a character once a sequence is complete, and
+    /// `REPLACEMENT_CHARACTER` for invalid or aborted sequences.
+    pub fn advance(&mut self, byte: u8) -> Option<char> {
+        match byte {
+            0x80...0xbf => {
+                if self.remaining == 0 {
+                    return Some(REPLACEMENT_CHARACTER);
+                }
+                self.code_point = self.code_point << 6 | (byte & 0x3f) as u32;
+                self.remaining -= 1;
+                if self.remaining != 0 {
+                    return None;
+                }
+                if self.code_point < self.min {
+                    Some(REPLACEMENT_CHARACTER)
+                } else {
+                    Some(::core::char::from_u32(self.code_point).unwrap_or(REPLACEMENT_CHARACTER))
+                }
+            }
+            0xc2...0xdf => self.start(1, (byte & 0x1f) as u32, 0x80),
+            0xe0...0xef => self.start(2, (byte & 0x0f) as u32, 0x800),
+            0xf0...0xf4 => self.start(3, (byte & 0x07) as u32, 0x10000),
+            _ => {
+                self.remaining = 0;
+                Some(REPLACEMENT_CHARACTER)
+            }
+        }
+    }
+
+    /// Starts a sequence with the specified number of continuation bytes.
+    ///
+    /// Returns `REPLACEMENT_CHARACTER` if it aborts the current sequence.
+    fn start(&mut self, remaining: u8, code_point: u32, min: u32) -> Option<char> {
+        let aborted = self.is_pending();
+        self.code_point = code_point;
+        self.remaining = remaining;
+        self.min = min;
+        if aborted {
+            Some(REPLACEMENT_CHARACTER)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,782 @@
+use core::cmp::min;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use collections::VecDeque;
+use spin::Mutex;
+use cpuio::{inb, outb};
+use memory::KERNEL_OFFSET;
+use dmesg;
+use interrupts;
+use keyboard::{self, Key, KeyEvent, SHIFT, ALT};
+use self::ansi::{Action, Csi, Parser};
+use self::cp437::{Utf8Decoder, REPLACEMENT_GLYPH};
+
+mod ansi;
+mod cp437;
+
+macro_rules! println {
+    ($fmt:expr) => (print!(concat!($fmt, "\n")));
+    ($fmt:expr, $($arg:tt)*) => (print!(concat!($fmt, "\n"), $($arg)*));
+}
+
+macro_rules! print {
+    ($($arg:tt)*) => ({
+            $crate::vga::print(format_args!($($arg)*));
+    });
+}
+
+/// The number of virtual consoles.
+pub const CONSOLE_COUNT: usize = 6;
+
+/// The first virtual console, used for kernel output.
+pub static Console: Mutex<Writer> = Mutex::new(Writer::new(0));
+
+/// The second virtual console.
+static CONSOLE2: Mutex<Writer> = Mutex::new(Writer::new(1));
+
+/// The third virtual console.
+static CONSOLE3: Mutex<Writer> = Mutex::new(Writer::new(2));
+
+/// The fourth virtual console.
+static CONSOLE4: Mutex<Writer> = Mutex::new(Writer::new(3));
+
+/// The fifth virtual console.
+static CONSOLE5: Mutex<Writer> = Mutex::new(Writer::new(4));
+
+/// The sixth virtual console.
+static CONSOLE6: Mutex<Writer> = Mutex::new(Writer::new(5));
+
+/// The index of the virtual console shown on the screen.
+static ACTIVE_CONSOLE: AtomicUsize = AtomicUsize::new(0);
+
+/// Prints to the console and records the output in the kernel message buffer.
+///
+/// Used by `print!` and `println!`.
+pub fn print(args: fmt::Arguments) {
+    Console.lock().write_fmt(args).unwrap();
+    dmesg::record(args);
+}
+
+/// The color used after a reset.
+const DEFAULT_COLOR: Color = Color::new(HalfColor::White, HalfColor::Black);
+
+/// A blank character in the default color.
+const BLANK: Character = Character {
+    char_code: b' ',
+    color: DEFAULT_COLOR,
+};
+
+/// The address of the VGA text buffer.
+const VGA_BUFFER: usize = KERNEL_OFFSET + 0xB8000;
+
+/// The VGA colors in the order of the ANSI color codes.
+const ANSI_COLORS: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+/// The buffer width.
+const BUFFER_WIDTH: usize = 80;
+
+/// The buffer height.
+const BUFFER_HEIGHT: usize = 25;
+
+/// The tab width.
+const TAB_WIDTH: usize = 4;
+
+/// The CRTC address register port.
+const CRTC_ADDRESS: u16 = 0x3D4;
+
+/// The CRTC data register port.
+const CRTC_DATA: u16 = 0x3D5;
+
+/// The cursor start register, holds the first scanline and the disable flag.
+const CRTC_CURSOR_START: u8 = 0x0A;
+
+/// The cursor end register, holds the last scanline.
+const CRTC_CURSOR_END: u8 = 0x0B;
+
+/// The high byte of the cursor location.
+const CRTC_CURSOR_LOCATION_HIGH: u8 = 0x0E;
+
+/// The low byte of the cursor location.
+const CRTC_CURSOR_LOCATION_LOW: u8 = 0x0F;
+
+/// The cursor disable flag of the cursor start register.
+const CURSOR_DISABLE: u8 = 1 << 5;
+
+/// The `HalfColor` type.
+///
+/// Represents a 4-bit color.
+#[repr(u8)]
+#[allow(dead_code)]
+pub enum HalfColor {
+    Black = 0,
+    Blue = 1,
+    Green = 2,
+    Cyan = 3,
+    Red = 4,
+    Magenta = 5,
+    Brown = 6,
+    LightGray = 7,
+    DarkGray = 8,
+    LightBlue = 9,
+    LightGreen = 10,
+    LightCyan = 11,
+    LightRed = 12,
+    Pink = 13,
+    Yellow = 14,
+    White = 15,
+}
+
+/// The `Color` type.
+#[derive(Copy, Clone)]
+pub struct Color(u8);
+
+/// The `Color` implementation.
+impl Color {
+    /// Constructs a new `Color`.
+    pub const fn new(foreground: HalfColor, background: HalfColor) -> Color {
+        Color((background as u8) << 4 | (foreground as u8))
+    }
+
+    /// Gets the foreground color as a 4-bit value.
+    fn foreground(&self) -> u8 {
+        self.0 & 0x0f
+    }
+
+    /// Gets the background color as a 4-bit value.
+    fn background(&self) -> u8 {
+        self.0 >> 4
+    }
+
+    /// Replaces the foreground color with a 4-bit value.
+    fn with_foreground(self, foreground: u8) -> Color {
+        Color(self.0 & 0xf0 | foreground & 0x0f)
+    }
+
+    /// Replaces the background color with a 4-bit value.
+    fn with_background(self, background: u8) -> Color {
+        Color(self.0 & 0x0f | (background & 0x0f) << 4)
+    }
+}
+
+/// The `Character` type.
+///
+/// Represents a character in the VGA buffer.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Character {
+    /// The CP437 character code.
+    char_code: u8,
+    /// The color byte.
+    color: Color,
+}
+
+/// The `Buffer` type.
+///
+/// Represents the contents of the VGA buffer.
+struct Buffer {
+    /// The characters.
+    chars: [[Character; BUFFER_WIDTH]; BUFFER_HEIGHT],
+}
+
+/// The `Writer` type.
+pub struct Writer {
+    /// The column.
+    col: usize,
+    /// The row.
+    row: usize,
+    /// The color.
+    color: Color,
+    /// The off-screen buffer, copied to the screen while the console is active.
+    buffer: Buffer,
+    /// The index of the virtual console.
+    index: usize,
+    /// Whether the hardware cursor is hidden.
+    cursor_hidden: bool,
+    /// The escape sequence parser.
+    parser: Parser,
+    /// The decoder of non-ASCII characters.
+    utf8: Utf8Decoder,
+    /// The cursor position saved by an escape sequence.
+    saved_cursor: (usize, usize),
+    /// Whether bold text is on, which brightens the foreground color.
+    bold: bool,
+    /// Whether reverse video is on, which swaps the foreground and background colors.
+    reverse: bool,
+    /// The lines scrolled off the top of the screen, oldest first.
+    scrollback: Option<VecDeque<[Character; BUFFER_WIDTH]>>,
+    /// The maximum number of lines in the scrollback.
+    scrollback_size: usize,
+    /// The number of lines the view is scrolled back.
+    view_offset: usize,
+}
+
+/// The `::core::fmt::Write` implementation for `Writer`.
+impl ::core::fmt::Write for Writer {
+    #[inline(always)]
+    fn write_str(&mut self, string: &str) -> ::core::fmt::Result {
+        Writer::write_str(self, string);
+        Ok(())
+    }
+}
+
+/// The `Writer` implementation.
+impl Writer {
+    /// Constructs a new blank `Writer` for the specified virtual console.
+    const fn new(index: usize) -> Writer {
+        Writer {
+            col: 0,
+            row: 0,
+            color: DEFAULT_COLOR,
+            buffer: Buffer { chars: [[BLANK; BUFFER_WIDTH]; BUFFER_HEIGHT] },
+            index: index,
+            cursor_hidden: false,
+            parser: Parser::new(),
+            utf8: Utf8Decoder::new(),
+            saved_cursor: (0, 0),
+            bold: false,
+            reverse: false,
+            scrollback: None,
+            scrollback_size: 0,
+            view_offset: 0,
+        }
+    }
+
+    /// Tests if the console is shown on the screen.
+    pub fn is_active(&self) -> bool {
+        ACTIVE_CONSOLE.load(Ordering::SeqCst) == self.index
+    }
+
+    /// Copies the buffer to the screen and moves the hardware cursor,
+    /// if the console is active.
+    fn refresh(&self) {
+        // Test and copy at once, so a console switch can't land in between
+        interrupts::without_interrupts(|| {
+            if self.is_active() {
+                self.blit();
+                self.update_cursor();
+            }
+        });
+    }
+
+    /// Copies the buffer to the screen, scrolled back into the scrollback
+    /// by the view offset.
+    fn blit(&self) {
+        let screen = unsafe { &mut *(VGA_BUFFER as *mut Buffer) };
+        let history = self.scrollback.as_ref().map_or(0, |scrollback| scrollback.len());
+        let top = history - self.view_offset;
+        for row in 0..BUFFER_HEIGHT {
+            let line = top + row;
+            screen.chars[row] = match self.scrollback {
+                Some(ref scrollback) if line < history => scrollback[line],
+                _ => self.buffer.chars[line - history],
+            };
+        }
+    }
+
+    /// Redraws the whole console, including the cursor visibility.
+    fn redraw(&self) {
+        let start = read_crtc(CRTC_CURSOR_START);
+        if self.cursor_hidden {
+            write_crtc(CRTC_CURSOR_START, start | CURSOR_DISABLE);
+        } else {
+            write_crtc(CRTC_CURSOR_START, start & !CURSOR_DISABLE);
+        }
+        self.refresh();
+    }
+
+    /// Writes a byte and moves the hardware cursor behind it.
+    #[inline(always)]
+    pub fn write_byte(&mut self, byte: u8) {
+        self.put_byte(byte);
+        self.refresh();
+    }
+
+    /// Writes a byte without moving the hardware cursor.
+    ///
+    /// Bytes belonging to ANSI/VT100 escape sequences are interpreted.
+    /// Other bytes are decoded as UTF-8 and drawn with CP437 glyphs.
+    fn put_byte(&mut self, byte: u8) {
+        if self.view_offset != 0 {
+            self.scroll_to_bottom();
+        }
+        match self.parser.advance(byte) {
+            Some(Action::Print(byte)) if byte >= 0x80 => {
+                if let Some(c) = self.utf8.advance(byte) {
+                    self.put_glyph(cp437::from_char(c));
+                }
+            }
+            Some(Action::Print(byte)) => {
+                if self.utf8.is_pending() {
+                    // The sequence was cut short
+                    self.utf8.reset();
+                    self.put_glyph(REPLACEMENT_GLYPH);
+                }
+                self.put_char(byte);
+            }
+            Some(Action::Csi(csi)) => self.execute_csi(&csi),
+            Some(Action::Escape(b'7')) => self.saved_cursor = (self.col, self.row),
+            Some(Action::Escape(b'8')) => {
+                let (col, row) = self.saved_cursor;
+                self.move_cursor(col, row);
+            }
+            Some(Action::Escape(b'c')) => {
+                self.reset_rendition();
+                self.clear_screen();
+            }
+            Some(Action::Escape(_)) | None => (),
+        }
+    }
+
+    /// Executes a control sequence.
+    fn execute_csi(&mut self, csi: &Csi) {
+        if csi.private {
+            // Private modes like hiding the cursor aren't supported
+            return;
+        }
+        let n = csi.param(0, 1) as usize;
+        let (col, row) = (self.col, self.row);
+        match csi.command {
+            b'A' => self.move_cursor(col, row.saturating_sub(n)),
+            b'B' => self.move_cursor(col, row + n),
+            b'C' => self.move_cursor(col + n, row),
+            b'D' => self.move_cursor(col.saturating_sub(n), row),
+            b'E' => self.move_cursor(0, row + n),
+            b'F' => self.move_cursor(0, row.saturating_sub(n)),
+            b'G' => self.move_cursor(n - 1, row),
+            b'H' | b'f' => {
+                let col = csi.param(1, 1) as usize - 1;
+                self.move_cursor(col, n - 1)
+            }
+            b'J' => {
+                match csi.param(0, 0) {
+                    0 => self.erase(col, row, BUFFER_WIDTH, BUFFER_HEIGHT - 1),
+                    1 => self.erase(0, 0, min(col + 1, BUFFER_WIDTH), row),
+                    _ => self.erase(0, 0, BUFFER_WIDTH, BUFFER_HEIGHT - 1),
+                }
+            }
+            b'K' => {
+                match csi.param(0, 0) {
+                    0 => self.erase(col, row, BUFFER_WIDTH, row),
+                    1 => self.erase(0, row, min(col + 1, BUFFER_WIDTH), row),
+                    _ => self.erase(0, row, BUFFER_WIDTH, row),
+                }
+            }
+            b'm' => self.select_graphic_rendition(csi),
+            b's' => self.saved_cursor = (col, row),
+            b'u' => {
+                let (col, row) = self.saved_cursor;
+                self.move_cursor(col, row);
+            }
+            _ => (),
+        }
+    }
+
+    /// Applies the color and intensity attributes of an SGR sequence.
+    fn select_graphic_rendition(&mut self, csi: &Csi) {
+        if csi.params().is_empty() {
+            self.reset_rendition();
+        }
+        let params = csi.params();
+        let mut i = 0;
+        while i < params.len() {
+            let param = params[i];
+            i += 1;
+            match param {
+                0 => self.reset_rendition(),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                7 => self.reverse = true,
+                27 => self.reverse = false,
+                30...37 => {
+                    let foreground = ANSI_COLORS[(param - 30) as usize];
+                    self.color = self.color.with_foreground(foreground);
+                }
+                39 => self.color = self.color.with_foreground(DEFAULT_COLOR.foreground()),
+                40...47 => {
+                    let background = ANSI_COLORS[(param - 40) as usize];
+                    self.color = self.color.with_background(background);
+                }
+                // Extended colors aren't supported, skip their arguments,
+                // 5;n for an indexed color or 2;r;g;b for a true color
+                38 | 48 => {
+                    i += match params.get(i) {
+                        Some(&5) => 2,
+                        Some(&2) => 4,
+                        _ => params.len() - i,
+                    };
+                }
+                49 => self.color = self.color.with_background(DEFAULT_COLOR.background()),
+                90...97 => {
+                    let foreground = ANSI_COLORS[(param - 90) as usize] | 0x08;
+                    self.color = self.color.with_foreground(foreground);
+                }
+                100...107 => {
+                    let background = ANSI_COLORS[(param - 100) as usize] | 0x08;
+                    self.color = self.color.with_background(background);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Resets the colors and turns bold and reverse video off.
+    fn reset_rendition(&mut self) {
+        self.color = DEFAULT_COLOR;
+        self.bold = false;
+        self.reverse = false;
+    }
+
+    /// Gets the color characters are drawn in,
+    /// with bold and reverse video applied.
+    fn draw_color(&self) -> Color {
+        let mut color = self.color;
+        if self.bold {
+            let foreground = color.foreground() | 0x08;
+            color = color.with_foreground(foreground);
+        }
+        if self.reverse {
+            let (foreground, background) = (color.foreground(), color.background());
+            color = color.with_foreground(background).with_background(foreground);
+        }
+        color
+    }
+
+    /// Moves the software cursor, clamped to the screen.
+    fn move_cursor(&mut self, col: usize, row: usize) {
+        self.col = if col >= BUFFER_WIDTH { BUFFER_WIDTH - 1 } else { col };
+        self.row = if row >= BUFFER_HEIGHT { BUFFER_HEIGHT - 1 } else { row };
+    }
+
+    /// Erases the characters from a start position up to,
+    /// but not including, an end column on an end row.
+    fn erase(&mut self, start_col: usize, start_row: usize, end_col: usize, end_row: usize) {
+        let blank = Character {
+            char_code: b' ',
+            color: self.draw_color(),
+        };
+        for row in start_row..end_row + 1 {
+            let from = if row == start_row { start_col } else { 0 };
+            let to = if row == end_row { end_col } else { BUFFER_WIDTH };
+            for col in from..to {
+                self.buffer().chars[row][col] = blank;
+            }
+        }
+    }
+
+    /// Writes a printable byte or executes a control character.
+    fn put_char(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.new_line(),
+            b'\r' => self.col = 0,
+            b'\t' => {
+                for _ in 0..(TAB_WIDTH - (self.col % TAB_WIDTH)) {
+                    self.put_char(b' ');
+                }
+            }
+            0x08 => {
+                // Backspace
+                let blank = Character {
+                    char_code: b' ',
+                    color: self.draw_color(),
+                };
+                if self.col == 0 && self.row == 0 {
+                    return;
+                } else if self.col == 0 {
+                    self.buffer().chars[self.row][self.col] = blank;
+                    self.row -= 1;
+                    self.col = BUFFER_WIDTH - 1;
+                } else {
+                    self.buffer().chars[self.row][self.col] = blank;
+                    self.col -= 1;
+                }
+            }
+            _ => self.put_glyph(byte),
+        }
+    }
+
+    /// Draws a CP437 glyph, including the ones sharing their code with
+    /// a control character.
+    fn put_glyph(&mut self, glyph: u8) {
+        if self.col >= BUFFER_WIDTH {
+            self.new_line();
+        }
+        self.buffer().chars[self.row][self.col] = Character {
+            char_code: glyph,
+            color: self.draw_color(),
+        };
+        self.col += 1;
+    }
+
+    /// Writes a string.
+    #[inline(always)]
+    pub fn write_str(&mut self, string: &str) {
+        for byte in string.bytes() {
+            self.put_byte(byte)
+        }
+        self.refresh();
+    }
+
+    /// Clears the screen.
+    ///
+    /// Also properly fills the screen with the current color.
+    #[inline(always)]
+    pub fn clear_screen(&mut self) {
+        self.scroll_to_bottom();
+        self.col = 0;
+        self.row = 0;
+        let blank = Character {
+            char_code: b' ',
+            color: self.draw_color(),
+        };
+        let buf = self.buffer();
+        for row in 0..BUFFER_HEIGHT {
+            for col in 0..BUFFER_WIDTH {
+                buf.chars[row][col] = blank;
+            }
+        }
+        self.refresh();
+    }
+
+    /// Sets the cursor to the specified position, clamped to the screen.
+    #[inline(always)]
+    pub fn set_cursor(&mut self, x: usize, y: usize) {
+        self.move_cursor(x, y);
+        self.refresh();
+    }
+
+    /// Shows the hardware cursor while the console is active.
+    pub fn show_cursor(&mut self) {
+        self.cursor_hidden = false;
+        if self.is_active() {
+            let start = read_crtc(CRTC_CURSOR_START);
+            write_crtc(CRTC_CURSOR_START, start & !CURSOR_DISABLE);
+        }
+    }
+
+    /// Hides the hardware cursor while the console is active.
+    pub fn hide_cursor(&mut self) {
+        self.cursor_hidden = true;
+        if self.is_active() {
+            let start = read_crtc(CRTC_CURSOR_START);
+            write_crtc(CRTC_CURSOR_START, start | CURSOR_DISABLE);
+        }
+    }
+
+    /// Sets the first and the last scanline of the hardware cursor.
+    ///
+    /// Characters are 16 scanlines high, so `(14, 15)` is an
+    /// underline and `(0, 15)` is a block.
+    pub fn set_cursor_shape(&mut self, start: u8, end: u8) {
+        let old_start = read_crtc(CRTC_CURSOR_START);
+        let old_end = read_crtc(CRTC_CURSOR_END);
+        write_crtc(CRTC_CURSOR_START, (old_start & 0xE0) | (start & 0x1F));
+        write_crtc(CRTC_CURSOR_END, (old_end & 0xE0) | (end & 0x1F));
+    }
+
+    /// Sets the number of lines kept in the scrollback.
+    ///
+    /// Zero disables the scrollback. Allocates every line at once,
+    /// so it needs the heap and must not run in an interrupt handler.
+    pub fn set_scrollback_size(&mut self, lines: usize) {
+        self.scroll_to_bottom();
+        self.scrollback_size = lines;
+        if lines == 0 {
+            self.scrollback = None;
+            return;
+        }
+        // Reserve every line up front, so scrolling never allocates
+        let mut scrollback = VecDeque::with_capacity(lines);
+        if let Some(old) = self.scrollback.take() {
+            let skipped = old.len().saturating_sub(lines);
+            scrollback.extend(old.into_iter().skip(skipped));
+        }
+        self.scrollback = Some(scrollback);
+    }
+
+    /// Scrolls the view back into the scrollback.
+    pub fn scroll_view_up(&mut self, lines: usize) {
+        let history = self.scrollback.as_ref().map_or(0, |scrollback| scrollback.len());
+        let offset = min(self.view_offset + lines, history);
+        self.set_view_offset(offset);
+    }
+
+    /// Scrolls the view forward towards the current screen.
+    pub fn scroll_view_down(&mut self, lines: usize) {
+        let offset = self.view_offset.saturating_sub(lines);
+        self.set_view_offset(offset);
+    }
+
+    /// Scrolls the view back to the current screen.
+    pub fn scroll_to_bottom(&mut self) {
+        self.set_view_offset(0);
+    }
+
+    /// Scrolls the view back into the scrollback by the specified number of lines.
+    fn set_view_offset(&mut self, offset: usize) {
+        if offset != self.view_offset {
+            self.view_offset = offset;
+            self.refresh();
+        }
+    }
+
+    /// Moves the hardware cursor to the software cursor.
+    ///
+    /// Moves it off the screen while the view is scrolled back.
+    fn update_cursor(&self) {
+        let col = if self.col >= BUFFER_WIDTH { BUFFER_WIDTH - 1 } else { self.col };
+        let row = if self.row >= BUFFER_HEIGHT { BUFFER_HEIGHT - 1 } else { self.row };
+        let position = if self.view_offset == 0 {
+            row * BUFFER_WIDTH + col
+        } else {
+            BUFFER_WIDTH * BUFFER_HEIGHT
+        };
+        write_crtc(CRTC_CURSOR_LOCATION_LOW, position as u8);
+        write_crtc(CRTC_CURSOR_LOCATION_HIGH, (position >> 8) as u8);
+    }
+
+    /// Gets the foreground and background color characters are drawn in.
+    #[inline(always)]
+    pub fn color(&self) -> Color {
+        self.draw_color()
+    }
+
+    /// Sets the foreground and background color.
+    ///
+    /// Turns bold and reverse video off, so characters are drawn in that color.
+    #[inline(always)]
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+        self.bold = false;
+        self.reverse = false;
+    }
+
+    /// Starts a new line.
+    #[inline(always)]
+    fn new_line(&mut self) {
+        self.col = 0;
+        if self.row < BUFFER_HEIGHT - 1 {
+            self.row += 1;
+        } else {
+            self.scroll();
+        }
+    }
+
+    /// Scrolls up by one line and clears the last line.
+    #[inline(always)]
+    fn scroll(&mut self) {
+        let blank = Character {
+            char_code: b' ',
+            color: self.draw_color(),
+        };
+        let top = self.buffer().chars[0];
+        // The scrollback never grows past the capacity reserved by
+        // `set_scrollback_size`, since this may run in an interrupt handler
+        // that must not take the heap lock
+        if let Some(ref mut scrollback) = self.scrollback {
+            if scrollback.len() >= self.scrollback_size {
+                scrollback.pop_front();
+            }
+            scrollback.push_back(top);
+        }
+        for y in 0..(BUFFER_HEIGHT - 1) {
+            for x in 0..BUFFER_WIDTH {
+                self.buffer().chars[y][x] = self.buffer().chars[y + 1][x];
+            }
+        }
+        self.buffer().chars[BUFFER_HEIGHT - 1] = [blank; BUFFER_WIDTH];
+    }
+
+    /// Gets a mutable reference to the buffer.
+    #[inline(always)]
+    fn buffer(&mut self) -> &mut Buffer {
+        &mut self.buffer
+    }
+}
+
+/// Reads a CRTC register.
+fn read_crtc(register: u8) -> u8 {
+    unsafe {
+        outb(register, CRTC_ADDRESS);
+        inb(CRTC_DATA)
+    }
+}
+
+/// Writes a CRTC register.
+fn write_crtc(register: u8, value: u8) {
+    unsafe {
+        outb(register, CRTC_ADDRESS);
+        outb(value, CRTC_DATA);
+    }
+}
+
+/// Gets a virtual console.
+pub fn console(index: usize) -> &'static Mutex<Writer> {
+    match index {
+        0 => &Console,
+        1 => &CONSOLE2,
+        2 => &CONSOLE3,
+        3 => &CONSOLE4,
+        4 => &CONSOLE5,
+        5 => &CONSOLE6,
+        _ => panic!("Invalid virtual console {}", index),
+    }
+}
+
+/// Gets the index of the virtual console shown on the screen.
+pub fn active_console() -> usize {
+    ACTIVE_CONSOLE.load(Ordering::SeqCst)
+}
+
+/// Shows the specified virtual console on the screen.
+///
+/// A console locked by the interrupted code is drawn on its next output.
+pub fn switch_console(index: usize) {
+    assert!(index < CONSOLE_COUNT, "Invalid virtual console {}", index);
+    interrupts::without_interrupts(|| {
+        ACTIVE_CONSOLE.store(index, Ordering::SeqCst);
+        if let Some(console) = console(index).try_lock() {
+            console.redraw();
+        }
+    });
+}
+
+/// Enables the scrollback of the first virtual console and the hotkeys,
+/// Shift+PageUp/PageDown to page through the scrollback and
+/// Alt+F1..F6 to switch consoles.
+///
+/// Only the first console, which the kernel logs to, gets a scrollback,
+/// since every line of it is allocated up front. The other consoles can
+/// enable theirs with `Writer::set_scrollback_size`.
+pub fn init(scrollback_lines: usize) {
+    Console.lock().set_scrollback_size(scrollback_lines);
+    keyboard::register_hotkey(Key::PageUp, SHIFT, scroll_page_up);
+    keyboard::register_hotkey(Key::PageDown, SHIFT, scroll_page_down);
+    for index in 0..CONSOLE_COUNT {
+        keyboard::register_hotkey(Key::Function(index as u8 + 1), ALT, switch_console_hotkey);
+    }
+}
+
+/// Pages the active console view back.
+///
+/// Skipped if the console is locked by the interrupted code.
+fn scroll_page_up(_event: KeyEvent) {
+    if let Some(mut console) = console(active_console()).try_lock() {
+        console.scroll_view_up(BUFFER_HEIGHT / 2);
+    }
+}
+
+/// Pages the active console view forward.
+///
+/// Skipped if the console is locked by the interrupted code.
+fn scroll_page_down(_event: KeyEvent) {
+    if let Some(mut console) = console(active_console()).try_lock() {
+        console.scroll_view_down(BUFFER_HEIGHT / 2);
+    }
+}
+
+/// Switches to the virtual console of the pressed function key.
+fn switch_console_hotkey(event: KeyEvent) {
+    if let Key::Function(number) = event.key {
+        switch_console(number as usize - 1);
+    }
+}